@@ -3,8 +3,8 @@
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 use super::state::{
-    AppState, Mode, RuleEditorField, RuleEditorState, SettingsItem, View, WatchEditorField,
-    WatchEditorState,
+    AppState, LogLevel, Mode, RuleEditorField, RuleEditorState, SettingsItem, View,
+    WatchEditorField, WatchEditorState,
 };
 #[cfg(unix)]
 use crate::autostart;
@@ -202,6 +202,7 @@ fn handle_dashboard_key(state: &mut AppState, key: KeyEvent) {
         KeyCode::Char('r') => state.view = View::Rules,
         KeyCode::Char('w') => state.view = View::Watches,
         KeyCode::Char('l') => state.view = View::Log,
+        #[allow(clippy::collapsible_match)]
         KeyCode::Char('u') | KeyCode::Char('U') => {
             if state.update_available.is_some() {
                 state.mode = Mode::UpdateConfirm;
@@ -270,6 +271,9 @@ fn handle_rules_key(state: &mut AppState, key: KeyEvent) {
                 state.set_status("Select a rule first");
             }
         }
+        KeyCode::Char('p') => {
+            state.preview_selected_rule();
+        }
         KeyCode::Char('d') | KeyCode::Delete => {
             // Delete selected rule
             if let Some(idx) = state.selected_rule {
@@ -386,7 +390,8 @@ fn handle_watches_key(state: &mut AppState, key: KeyEvent) {
 }
 
 fn handle_log_key(state: &mut AppState, key: KeyEvent) {
-    let len = state.log_entries.len();
+    // Navigation moves through the journal of performed actions
+    let len = state.journal.len();
 
     match key.code {
         KeyCode::Char('c') => {
@@ -394,30 +399,81 @@ fn handle_log_key(state: &mut AppState, key: KeyEvent) {
             state.log_scroll = 0;
             state.set_status("Log cleared");
         }
+        KeyCode::Char('u') => undo_selected_action(state),
+        _ if len == 0 => {}
         KeyCode::Up | KeyCode::Char('k') => {
-            state.log_scroll = state.log_scroll.saturating_sub(1);
+            state.selected_journal = Some(
+                state
+                    .selected_journal
+                    .map(|i| i.saturating_sub(1))
+                    .unwrap_or(0),
+            );
         }
         KeyCode::Down | KeyCode::Char('j') => {
-            if state.log_scroll < len.saturating_sub(1) {
-                state.log_scroll += 1;
-            }
+            state.selected_journal = Some(
+                state
+                    .selected_journal
+                    .map(|i| (i + 1).min(len - 1))
+                    .unwrap_or(0),
+            );
         }
         KeyCode::PageUp => {
-            state.log_scroll = state.log_scroll.saturating_sub(10);
+            state.selected_journal = Some(state.selected_journal.unwrap_or(0).saturating_sub(10));
         }
         KeyCode::PageDown => {
-            state.log_scroll = (state.log_scroll + 10).min(len.saturating_sub(1));
+            state.selected_journal = Some((state.selected_journal.unwrap_or(0) + 10).min(len - 1));
         }
         KeyCode::Home | KeyCode::Char('g') => {
-            state.log_scroll = 0;
+            state.selected_journal = Some(0);
         }
         KeyCode::End | KeyCode::Char('G') => {
-            state.log_scroll = len.saturating_sub(1);
+            state.selected_journal = Some(len - 1);
         }
         _ => {}
     }
 }
 
+/// Undo the journal entry selected in the Log view
+fn undo_selected_action(state: &mut AppState) {
+    let Some(entry) = state
+        .selected_journal
+        .and_then(|i| state.journal.get(i))
+        .cloned()
+    else {
+        state.set_status("Select an action to undo");
+        return;
+    };
+    if !entry.can_undo() {
+        let reason = if entry.undone {
+            "Already undone".to_string()
+        } else {
+            format!("{} actions can't be undone", entry.action)
+        };
+        state.set_status(reason);
+        return;
+    }
+    let Some(journal) = crate::journal::journal_path() else {
+        return;
+    };
+
+    let description = entry.describe();
+    match crate::journal::undo(&journal, vec![entry]) {
+        Ok(results) => match results.into_iter().next() {
+            Some((_, Ok(()))) => {
+                state.log(LogLevel::Success, format!("Undid {}", description));
+                state.set_status("Action undone");
+            }
+            Some((_, Err(e))) => {
+                state.log(LogLevel::Error, format!("Undo failed: {:#}", e));
+                state.set_status(format!("Undo failed: {:#}", e));
+            }
+            None => {}
+        },
+        Err(e) => state.set_status(format!("Undo failed: {:#}", e)),
+    }
+    state.load_journal();
+}
+
 fn handle_about_key(state: &mut AppState, key: KeyEvent) {
     match key.code {
         KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => {
@@ -692,6 +748,11 @@ fn handle_rule_editor_key(state: &mut AppState, key: KeyEvent) {
                 return;
             }
 
+            if let Some(error) = editor.validation_error() {
+                state.set_status(error);
+                return;
+            }
+
             let rule = editor.to_rule();
             let rule_name = rule.name.clone();
 
@@ -741,19 +802,19 @@ fn handle_rule_editor_field_input(editor: &mut RuleEditorState, key: KeyEvent) {
         RuleEditorField::NameRegex => {
             handle_text_input(&mut editor.name_regex, &mut editor.cursor_name_regex, key)
         }
-        RuleEditorField::SizeGreater => handle_numeric_input(
+        RuleEditorField::SizeGreater => handle_quantity_input(
             &mut editor.size_greater,
             &mut editor.cursor_size_greater,
             key,
         ),
         RuleEditorField::SizeLess => {
-            handle_numeric_input(&mut editor.size_less, &mut editor.cursor_size_less, key)
+            handle_quantity_input(&mut editor.size_less, &mut editor.cursor_size_less, key)
         }
         RuleEditorField::AgeGreater => {
-            handle_numeric_input(&mut editor.age_greater, &mut editor.cursor_age_greater, key)
+            handle_quantity_input(&mut editor.age_greater, &mut editor.cursor_age_greater, key)
         }
         RuleEditorField::AgeLess => {
-            handle_numeric_input(&mut editor.age_less, &mut editor.cursor_age_less, key)
+            handle_quantity_input(&mut editor.age_less, &mut editor.cursor_age_less, key)
         }
         RuleEditorField::IsDirectory => {
             if matches!(
@@ -812,22 +873,26 @@ fn handle_text_input(input: &mut String, cursor: &mut usize, key: KeyEvent) {
             input.insert(*cursor, c);
             *cursor += 1;
         }
+        #[allow(clippy::collapsible_match)]
         KeyCode::Backspace => {
             if *cursor > 0 {
                 *cursor -= 1;
                 input.remove(*cursor);
             }
         }
+        #[allow(clippy::collapsible_match)]
         KeyCode::Delete => {
             if *cursor < input.len() {
                 input.remove(*cursor);
             }
         }
+        #[allow(clippy::collapsible_match)]
         KeyCode::Left => {
             if *cursor > 0 {
                 *cursor -= 1;
             }
         }
+        #[allow(clippy::collapsible_match)]
         KeyCode::Right => {
             if *cursor < input.len() {
                 *cursor += 1;
@@ -843,28 +908,33 @@ fn handle_text_input(input: &mut String, cursor: &mut usize, key: KeyEvent) {
     }
 }
 
-fn handle_numeric_input(input: &mut String, cursor: &mut usize, key: KeyEvent) {
+/// Input for numbers with an optional unit, like "25MB" or "36h"
+fn handle_quantity_input(input: &mut String, cursor: &mut usize, key: KeyEvent) {
     match key.code {
-        KeyCode::Char(c) if c.is_ascii_digit() => {
+        KeyCode::Char(c) if c.is_ascii_alphanumeric() || c == '.' => {
             input.insert(*cursor, c);
             *cursor += 1;
         }
+        #[allow(clippy::collapsible_match)]
         KeyCode::Backspace => {
             if *cursor > 0 {
                 *cursor -= 1;
                 input.remove(*cursor);
             }
         }
+        #[allow(clippy::collapsible_match)]
         KeyCode::Delete => {
             if *cursor < input.len() {
                 input.remove(*cursor);
             }
         }
+        #[allow(clippy::collapsible_match)]
         KeyCode::Left => {
             if *cursor > 0 {
                 *cursor -= 1;
             }
         }
+        #[allow(clippy::collapsible_match)]
         KeyCode::Right => {
             if *cursor < input.len() {
                 *cursor += 1;
@@ -957,6 +1027,7 @@ fn handle_watch_editor_field_input(editor: &mut WatchEditorState, key: KeyEvent)
                 KeyCode::Up | KeyCode::Char('k') => {
                     editor.rules_cursor = editor.rules_cursor.saturating_sub(1);
                 }
+                #[allow(clippy::collapsible_match)]
                 KeyCode::Down | KeyCode::Char('j') => {
                     if editor.rules_cursor < rule_count.saturating_sub(1) {
                         editor.rules_cursor += 1;
@@ -1,7 +1,9 @@
 //! Application state management
 
 use crate::config::Config;
-use crate::rules::{Action, Condition, Rule};
+use crate::rules::{
+    Action, Age, ArchiveFormat, ByteSize, Condition, ConflictStrategy, Rule, Schedule,
+};
 use crate::theme::Theme;
 use std::path::PathBuf;
 
@@ -161,6 +163,21 @@ pub struct AppState {
     /// Whether daemon is currently running
     pub daemon_running: bool,
 
+    /// Watch health last reported by the daemon
+    pub watch_statuses: Vec<crate::watcher::WatchStatus>,
+
+    /// Actions rules performed, newest first
+    pub journal: Vec<crate::journal::JournalEntry>,
+
+    /// Currently selected journal entry index
+    pub selected_journal: Option<usize>,
+
+    /// Modification time and size of the journal when it was last read
+    journal_stamp: Option<(std::time::SystemTime, u64)>,
+
+    /// Dry-run preview of a rule, shown in the rules view
+    pub rule_preview: Option<RulePreview>,
+
     /// Rule editor state
     pub rule_editor: Option<RuleEditorState>,
 
@@ -208,6 +225,17 @@ pub enum LogLevel {
     Error,
 }
 
+/// What a rule would do to the files in the watched folders right now
+#[derive(Debug, Clone)]
+pub struct RulePreview {
+    /// Rule the preview was made for
+    pub rule: String,
+
+    /// Plans of the files the rule would act on, with every step (other rules'
+    /// steps included)
+    pub plans: Vec<crate::rules::FilePlan>,
+}
+
 impl AppState {
     /// Create a new application state from config
     pub fn new(config: Config, theme: Theme) -> Self {
@@ -233,6 +261,11 @@ impl AppState {
             theme_picker_index,
             settings_index: 0,
             daemon_running: is_daemon_running(),
+            watch_statuses: Vec::new(),
+            journal: Vec::new(),
+            selected_journal: None,
+            journal_stamp: None,
+            rule_preview: None,
             rule_editor: None,
             watch_editor: None,
             update_available: None,
@@ -251,6 +284,8 @@ impl AppState {
                 state.config.watches.len()
             ),
         );
+        state.load_watch_statuses();
+        state.load_journal();
 
         state
     }
@@ -367,7 +402,125 @@ impl AppState {
         // Refresh daemon logs every ~2 seconds (20 frames at 100ms poll)
         if self.frame.is_multiple_of(20) {
             self.load_daemon_logs();
+            self.load_watch_statuses();
+            self.load_journal();
+        }
+    }
+
+    /// Load the action journal if it changed, keeping the selection on the
+    /// same entry
+    pub fn load_journal(&mut self) {
+        let stamp = crate::journal::journal_path()
+            .and_then(|path| std::fs::metadata(path).ok())
+            .and_then(|m| Some((m.modified().ok()?, m.len())));
+        if stamp.is_some() && stamp == self.journal_stamp {
+            return;
+        }
+        self.journal_stamp = stamp;
+
+        let selected = self
+            .selected_journal
+            .and_then(|i| self.journal.get(i))
+            .cloned();
+        let mut journal = crate::journal::list().unwrap_or_default();
+        journal.reverse();
+        journal.truncate(self.config.general.log_retention);
+        self.journal = journal;
+
+        self.selected_journal = match selected {
+            Some(selected) => self
+                .journal
+                .iter()
+                .position(|e| e.at == selected.at && e.source == selected.source)
+                .or(Some(0)),
+            None => None,
+        }
+        .filter(|_| !self.journal.is_empty());
+    }
+
+    /// Load the watch health the daemon writes to the state directory
+    pub fn load_watch_statuses(&mut self) {
+        self.watch_statuses = crate::watcher::WatchStatus::default_file()
+            .map(|file| crate::watcher::WatchStatus::load(&file))
+            .unwrap_or_default();
+    }
+
+    /// The daemon's view of a watch, if it is running and reported one
+    pub fn watch_status(
+        &self,
+        watch: &crate::config::WatchConfig,
+    ) -> Option<&crate::watcher::WatchStatus> {
+        if !self.daemon_running {
+            return None;
         }
+        let path = crate::expand_path(&watch.path);
+        self.watch_statuses
+            .iter()
+            .find(|status| status.path == path)
+    }
+
+    /// Preview what the selected rule would do to files in the watched folders
+    ///
+    /// Nothing is changed on disk. A disabled rule is previewed as if enabled.
+    pub fn preview_selected_rule(&mut self) {
+        let Some(rule) = self.current_rule().cloned() else {
+            self.set_status("Select a rule first");
+            return;
+        };
+
+        let rules = self
+            .config
+            .rules
+            .iter()
+            .cloned()
+            .map(|mut r| {
+                r.enabled |= r.name == rule.name;
+                r
+            })
+            .collect();
+        let engine = crate::RuleEngine::new(rules);
+        let added = crate::watcher::AddedIndex::load_default();
+        let watches = self.config.watches.clone();
+
+        let mut plans = Vec::new();
+        for watch in &watches {
+            let dir = crate::expand_path(&watch.path);
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if !path.is_file() {
+                    continue;
+                }
+
+                let base =
+                    crate::rules::MatchContext::with_root(Some(&dir)).with_added(added.get(&path));
+                match engine.plan_with(&path, &base, |r| {
+                    crate::config::rule_applies(&watches, &path, &r.name)
+                }) {
+                    Ok(plan) if plan.rules().contains(&rule.name.as_str()) => plans.push(plan),
+                    Ok(_) => {}
+                    Err(e) => {
+                        self.log(
+                            LogLevel::Warning,
+                            format!("Preview failed for {}: {:#}", path.display(), e),
+                        );
+                    }
+                }
+            }
+        }
+
+        self.set_status(format!(
+            "'{}' would act on {} file{}",
+            rule.name,
+            plans.len(),
+            if plans.len() == 1 { "" } else { "s" }
+        ));
+        self.rule_preview = Some(RulePreview {
+            rule: rule.name,
+            plans,
+        });
     }
 }
 
@@ -449,6 +602,7 @@ pub enum ActionTypeSelection {
     Delete,
     Run,
     Archive,
+    Extract,
     Nothing,
 }
 
@@ -462,6 +616,7 @@ impl ActionTypeSelection {
             Self::Delete,
             Self::Run,
             Self::Archive,
+            Self::Extract,
             Self::Nothing,
         ]
     }
@@ -475,6 +630,7 @@ impl ActionTypeSelection {
             Self::Delete => "Delete",
             Self::Run => "Run Command",
             Self::Archive => "Archive",
+            Self::Extract => "Extract",
             Self::Nothing => "Nothing",
         }
     }
@@ -487,7 +643,8 @@ impl ActionTypeSelection {
             Self::Trash => Self::Delete,
             Self::Delete => Self::Run,
             Self::Run => Self::Archive,
-            Self::Archive => Self::Nothing,
+            Self::Archive => Self::Extract,
+            Self::Extract => Self::Nothing,
             Self::Nothing => Self::Move,
         }
     }
@@ -501,7 +658,8 @@ impl ActionTypeSelection {
             Self::Delete => Self::Trash,
             Self::Run => Self::Delete,
             Self::Archive => Self::Run,
-            Self::Nothing => Self::Archive,
+            Self::Extract => Self::Archive,
+            Self::Nothing => Self::Extract,
         }
     }
 }
@@ -559,6 +717,9 @@ pub struct WatchEditorState {
 
     /// Cursor position for path field
     pub cursor_path: usize,
+
+    /// Original watch, keeps settings the editor doesn't expose (e.g. rescan)
+    pub original: crate::config::WatchConfig,
 }
 
 impl WatchEditorState {
@@ -573,6 +734,7 @@ impl WatchEditorState {
             available_rules,
             rules_cursor: 0,
             cursor_path: 0,
+            original: Default::default(),
         }
     }
 
@@ -593,6 +755,7 @@ impl WatchEditorState {
             available_rules,
             rules_cursor: 0,
             cursor_path,
+            original: watch.clone(),
         }
     }
 
@@ -616,6 +779,7 @@ impl WatchEditorState {
             path: std::path::PathBuf::from(&self.path),
             recursive: self.recursive,
             rules: self.rules_filter.clone(),
+            ..self.original.clone()
         }
     }
 }
@@ -645,6 +809,15 @@ pub struct RuleEditorState {
     pub is_directory: Option<bool>,
     pub is_hidden: Option<bool>,
 
+    /// Original condition, keeps fields the editor doesn't expose (e.g. any/all/not groups)
+    pub original_condition: Condition,
+
+    /// Pipeline steps after the first action, which the editor doesn't expose
+    pub extra_actions: Vec<Action>,
+
+    /// Rescan schedule, which the editor doesn't expose
+    pub rescan: Option<Schedule>,
+
     // Action fields
     pub action_type: ActionTypeSelection,
     pub action_destination: String,
@@ -653,6 +826,11 @@ pub struct RuleEditorState {
     pub action_args: String,
     pub action_overwrite: bool,
     pub action_delete_original: bool,
+    pub action_archive_format: ArchiveFormat,
+    pub action_archive_level: Option<i32>,
+    pub action_max_size: Option<u64>,
+    pub action_conflict: ConflictStrategy,
+    pub action_conflict_pattern: Option<String>,
 
     // Cursor positions for text fields
     pub cursor_name: usize,
@@ -680,6 +858,10 @@ impl RuleEditorState {
 
     /// Create editor state from an existing rule
     pub fn from_rule(index: usize, rule: &Rule) -> Self {
+        // The editor edits the first step; later pipeline steps are kept as-is
+        let nothing = Action::Nothing;
+        let first_action = rule.actions.first().unwrap_or(&nothing);
+
         let (
             action_type,
             action_destination,
@@ -688,7 +870,7 @@ impl RuleEditorState {
             action_args,
             action_overwrite,
             action_delete_original,
-        ) = match &rule.action {
+        ) = match first_action {
             Action::Move {
                 destination,
                 overwrite,
@@ -715,7 +897,7 @@ impl RuleEditorState {
                 *overwrite,
                 false,
             ),
-            Action::Rename { pattern } => (
+            Action::Rename { pattern, .. } => (
                 ActionTypeSelection::Rename,
                 String::new(),
                 pattern.clone(),
@@ -754,6 +936,7 @@ impl RuleEditorState {
             Action::Archive {
                 destination,
                 delete_original,
+                ..
             } => (
                 ActionTypeSelection::Archive,
                 destination
@@ -766,6 +949,22 @@ impl RuleEditorState {
                 false,
                 *delete_original,
             ),
+            Action::Extract {
+                destination,
+                delete_archive,
+                ..
+            } => (
+                ActionTypeSelection::Extract,
+                destination
+                    .as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default(),
+                String::new(),
+                String::new(),
+                String::new(),
+                false,
+                *delete_archive,
+            ),
             Action::Nothing => (
                 ActionTypeSelection::Nothing,
                 String::new(),
@@ -777,6 +976,33 @@ impl RuleEditorState {
             ),
         };
 
+        let (action_archive_format, action_archive_level) = match first_action {
+            Action::Archive { format, level, .. } => (*format, *level),
+            _ => (ArchiveFormat::default(), None),
+        };
+        let action_max_size = match first_action {
+            Action::Extract { max_size, .. } => *max_size,
+            _ => None,
+        };
+        let (action_conflict, action_conflict_pattern) = match first_action {
+            Action::Move {
+                conflict,
+                conflict_pattern,
+                ..
+            }
+            | Action::Copy {
+                conflict,
+                conflict_pattern,
+                ..
+            }
+            | Action::Rename {
+                conflict,
+                conflict_pattern,
+                ..
+            } => (*conflict, conflict_pattern.clone()),
+            _ => (ConflictStrategy::default(), None),
+        };
+
         Self {
             field: RuleEditorField::Name,
             editing_index: Some(index),
@@ -808,6 +1034,9 @@ impl RuleEditorState {
                 .unwrap_or_default(),
             is_directory: rule.condition.is_directory,
             is_hidden: rule.condition.is_hidden,
+            original_condition: rule.condition.clone(),
+            extra_actions: rule.actions.iter().skip(1).cloned().collect(),
+            rescan: rule.rescan.clone(),
             action_type,
             action_destination: action_destination.clone(),
             action_pattern: action_pattern.clone(),
@@ -815,6 +1044,11 @@ impl RuleEditorState {
             action_args: action_args.clone(),
             action_overwrite,
             action_delete_original,
+            action_archive_format,
+            action_archive_level,
+            action_max_size,
+            action_conflict,
+            action_conflict_pattern,
             // Set cursor positions to end of each field
             cursor_name: rule.name.len(),
             cursor_extension: rule
@@ -862,6 +1096,25 @@ impl RuleEditorState {
         }
     }
 
+    /// Describe the first size or age field whose value can't be parsed
+    pub fn validation_error(&self) -> Option<String> {
+        for (label, value) in [("Size >", &self.size_greater), ("Size <", &self.size_less)] {
+            if !value.is_empty()
+                && let Err(e) = value.parse::<ByteSize>()
+            {
+                return Some(format!("{}: {}", label, e));
+            }
+        }
+        for (label, value) in [("Age >", &self.age_greater), ("Age <", &self.age_less)] {
+            if !value.is_empty()
+                && let Err(e) = value.parse::<Age>()
+            {
+                return Some(format!("{}: {}", label, e));
+            }
+        }
+        None
+    }
+
     /// Build a Rule from the editor state
     pub fn to_rule(&self) -> Rule {
         let condition = Condition {
@@ -870,7 +1123,6 @@ impl RuleEditorState {
             } else {
                 Some(self.extension.clone())
             },
-            extensions: Vec::new(),
             name_matches: if self.name_glob.is_empty() {
                 None
             } else {
@@ -887,6 +1139,7 @@ impl RuleEditorState {
             age_days_less_than: self.age_less.parse().ok(),
             is_directory: self.is_directory,
             is_hidden: self.is_hidden,
+            ..self.original_condition.clone()
         };
 
         let action = match self.action_type {
@@ -894,14 +1147,20 @@ impl RuleEditorState {
                 destination: PathBuf::from(&self.action_destination),
                 create_destination: true,
                 overwrite: self.action_overwrite,
+                conflict: self.action_conflict,
+                conflict_pattern: self.action_conflict_pattern.clone(),
             },
             ActionTypeSelection::Copy => Action::Copy {
                 destination: PathBuf::from(&self.action_destination),
                 create_destination: true,
                 overwrite: self.action_overwrite,
+                conflict: self.action_conflict,
+                conflict_pattern: self.action_conflict_pattern.clone(),
             },
             ActionTypeSelection::Rename => Action::Rename {
                 pattern: self.action_pattern.clone(),
+                conflict: self.action_conflict,
+                conflict_pattern: self.action_conflict_pattern.clone(),
             },
             ActionTypeSelection::Trash => Action::Trash,
             ActionTypeSelection::Delete => Action::Delete,
@@ -920,16 +1179,31 @@ impl RuleEditorState {
                     Some(PathBuf::from(&self.action_destination))
                 },
                 delete_original: self.action_delete_original,
+                format: self.action_archive_format,
+                level: self.action_archive_level,
+            },
+            ActionTypeSelection::Extract => Action::Extract {
+                destination: if self.action_destination.is_empty() {
+                    None
+                } else {
+                    Some(PathBuf::from(&self.action_destination))
+                },
+                max_size: self.action_max_size,
+                delete_archive: self.action_delete_original,
             },
             ActionTypeSelection::Nothing => Action::Nothing,
         };
 
+        let mut actions = vec![action];
+        actions.extend(self.extra_actions.iter().cloned());
+
         Rule {
             name: self.name.clone(),
             enabled: self.enabled,
             condition,
-            action,
+            actions,
             stop_processing: self.stop_processing,
+            rescan: self.rescan.clone(),
         }
     }
 }
@@ -5,7 +5,9 @@ use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, BorderType, Borders, Clear, List, ListItem, Paragraph, Tabs, Wrap},
+    widgets::{
+        Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph, Tabs, Wrap,
+    },
 };
 
 use super::state::{
@@ -13,7 +15,9 @@ use super::state::{
 };
 #[cfg(unix)]
 use crate::autostart;
-use crate::theme::Theme;
+use crate::rules::{Age, ByteSize};
+use crate::theme::{Theme, ThemeColors};
+use crate::watcher::WatchHealth;
 
 /// ASCII art logo for Hazelnut
 const LOGO: &str = r#"
@@ -420,20 +424,12 @@ fn render_rules(frame: &mut Frame, state: &AppState, area: Rect) {
             };
 
             // Build the rule line
-            let action_preview = match &rule.action {
-                crate::rules::Action::Move { destination, .. } => {
-                    format!("→ {}", destination.display())
-                }
-                crate::rules::Action::Copy { destination, .. } => {
-                    format!("⇒ {}", destination.display())
-                }
-                crate::rules::Action::Rename { pattern } => format!("✎ {}", pattern),
-                crate::rules::Action::Trash => "🗑 Trash".to_string(),
-                crate::rules::Action::Delete => "⚠ Delete".to_string(),
-                crate::rules::Action::Run { command, .. } => format!("$ {}", command),
-                crate::rules::Action::Archive { .. } => "📦 Archive".to_string(),
-                crate::rules::Action::Nothing => "∅ Nothing".to_string(),
-            };
+            let action_preview = rule
+                .actions
+                .iter()
+                .map(action_preview)
+                .collect::<Vec<_>>()
+                .join(", ");
 
             ListItem::new(Line::from(vec![
                 Span::styled(format!(" {} ", status_icon), status_style),
@@ -458,7 +454,122 @@ fn render_rules(frame: &mut Frame, state: &AppState, area: Rect) {
         )
         .highlight_style(colors.selected());
 
-    frame.render_widget(rules_list, area);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+        .split(area);
+
+    frame.render_widget(rules_list, chunks[0]);
+    render_rule_preview(frame, state, chunks[1]);
+}
+
+/// Dry-run plans for the selected rule
+fn render_rule_preview(frame: &mut Frame, state: &AppState, area: Rect) {
+    let colors = state.theme.colors();
+    let selected = state.current_rule().map(|r| r.name.as_str());
+    let preview = state
+        .rule_preview
+        .as_ref()
+        .filter(|p| Some(p.rule.as_str()) == selected);
+
+    let title = match preview {
+        Some(preview) => format!(
+            " Preview: {} ({} files) [p: refresh] ",
+            preview.rule,
+            preview.plans.len()
+        ),
+        None => " Preview [p] ".to_string(),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(colors.block())
+        .title(title)
+        .title_style(colors.text_primary());
+
+    let Some(preview) = preview.filter(|p| !p.plans.is_empty()) else {
+        let message = match preview {
+            Some(preview) => format!(
+                "  No files in the watched folders would be touched by '{}'",
+                preview.rule
+            ),
+            None => "  Press p to see what this rule would do to files in the watched folders"
+                .to_string(),
+        };
+        let empty = Paragraph::new(vec![
+            Line::from(""),
+            Line::styled(message, colors.text_dim()),
+            Line::styled("  Nothing is changed on disk", colors.text_muted()),
+        ])
+        .block(block);
+        frame.render_widget(empty, area);
+        return;
+    };
+
+    let mut items: Vec<ListItem> = Vec::new();
+    for plan in &preview.plans {
+        let name = plan
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        items.push(ListItem::new(Line::from(vec![
+            Span::styled(" 📄 ", colors.text_info()),
+            Span::styled(name, colors.text().add_modifier(Modifier::BOLD)),
+        ])));
+
+        for step in &plan.steps {
+            // Steps of other rules that would also run are dimmed
+            let style = if step.rule == preview.rule {
+                colors.text()
+            } else {
+                colors.text_dim()
+            };
+            let mut spans = vec![
+                Span::styled(format!("    {} ", step.rule), colors.text_primary()),
+                Span::styled(format!("{} → ", step.action), style),
+                Span::styled(step.target(), style),
+            ];
+            let notes = step.notes();
+            if !notes.is_empty() {
+                spans.push(Span::styled(
+                    format!("  ⚠ {}", notes),
+                    colors.text_warning(),
+                ));
+            }
+            items.push(ListItem::new(Line::from(spans)));
+        }
+
+        if let Some(ref failure) = plan.failure {
+            items.push(ListItem::new(Line::styled(
+                format!("    ✗ {}", failure),
+                colors.text_error(),
+            )));
+        }
+    }
+
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+/// Short one-line description of an action for the rules list
+fn action_preview(action: &crate::rules::Action) -> String {
+    match action {
+        crate::rules::Action::Move { destination, .. } => {
+            format!("→ {}", destination.display())
+        }
+        crate::rules::Action::Copy { destination, .. } => {
+            format!("⇒ {}", destination.display())
+        }
+        crate::rules::Action::Rename { pattern, .. } => format!("✎ {}", pattern),
+        crate::rules::Action::Trash => "🗑 Trash".to_string(),
+        crate::rules::Action::Delete => "⚠ Delete".to_string(),
+        crate::rules::Action::Run { command, .. } => format!("$ {}", command),
+        crate::rules::Action::Archive { format, .. } => format!("📦 Archive ({})", format),
+        crate::rules::Action::Extract { destination, .. } => match destination {
+            Some(dest) => format!("📂 Extract → {}", dest.display()),
+            None => "📂 Extract".to_string(),
+        },
+        crate::rules::Action::Nothing => "∅ Nothing".to_string(),
+    }
 }
 
 fn render_watches(frame: &mut Frame, state: &AppState, area: Rect) {
@@ -503,17 +614,28 @@ fn render_watches(frame: &mut Frame, state: &AppState, area: Rect) {
             let recursive_indicator = if watch.recursive { " (recursive)" } else { "" };
             let path_str = watch.path.display().to_string();
 
-            // Check if path exists
-            let (icon, path_style) = if watch.path.exists() {
-                ("📁", colors.text())
-            } else {
-                ("⚠", colors.text_warning())
+            // Use the daemon's view of the watch, or check if the path exists
+            let (icon, path_style, health) = match state.watch_status(watch) {
+                Some(status) => match &status.health {
+                    WatchHealth::Active => ("📁", colors.text(), String::new()),
+                    WatchHealth::Missing => (
+                        "⚠",
+                        colors.text_warning(),
+                        format!(" missing since {}", status.since.format("%H:%M")),
+                    ),
+                    WatchHealth::Error(e) => ("✗", colors.text_error(), format!(" error: {}", e)),
+                },
+                None if crate::expand_path(&watch.path).exists() => {
+                    ("📁", colors.text(), String::new())
+                }
+                None => ("⚠", colors.text_warning(), String::new()),
             };
 
             ListItem::new(Line::from(vec![
                 Span::styled(format!(" {} ", icon), base_style),
                 Span::styled(path_str, path_style),
                 Span::styled(recursive_indicator, colors.text_muted()),
+                Span::styled(health, path_style),
             ]))
             .style(base_style)
         })
@@ -535,6 +657,16 @@ fn render_watches(frame: &mut Frame, state: &AppState, area: Rect) {
 }
 
 fn render_log(frame: &mut Frame, state: &AppState, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+
+    render_activity_log(frame, state, chunks[0]);
+    render_journal(frame, state, chunks[1]);
+}
+
+fn render_activity_log(frame: &mut Frame, state: &AppState, area: Rect) {
     let colors = state.theme.colors();
 
     if state.log_entries.is_empty() {
@@ -596,6 +728,73 @@ fn render_log(frame: &mut Frame, state: &AppState, area: Rect) {
     frame.render_widget(list, area);
 }
 
+fn render_journal(frame: &mut Frame, state: &AppState, area: Rect) {
+    let colors = state.theme.colors();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(if state.view == View::Log {
+            colors.block_focus()
+        } else {
+            colors.block()
+        })
+        .title(format!(" Actions ({}) [u: undo] ", state.journal.len()))
+        .title_style(colors.text_primary());
+
+    if state.journal.is_empty() {
+        let empty = Paragraph::new(vec![
+            Line::from(""),
+            Line::styled("  No actions yet", colors.text_muted()),
+            Line::from(""),
+            Line::styled(
+                "  Files moved, renamed, copied or trashed by rules show up here",
+                colors.text_dim(),
+            ),
+        ])
+        .block(block);
+        frame.render_widget(empty, area);
+        return;
+    }
+
+    let items: Vec<ListItem> = state
+        .journal
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let is_selected = state.selected_journal == Some(i);
+            let base_style = if is_selected {
+                colors.selected()
+            } else {
+                colors.text()
+            };
+            let (icon, text_style) = if entry.undone {
+                ("↩", colors.text_muted())
+            } else if entry.can_undo() {
+                ("●", colors.text())
+            } else {
+                ("·", colors.text_dim())
+            };
+
+            ListItem::new(Line::from(vec![
+                Span::styled(format!(" {} ", icon), colors.text_info()),
+                Span::styled(
+                    format!("[{}] ", entry.at.format("%m-%d %H:%M:%S")),
+                    colors.text_muted(),
+                ),
+                Span::styled(format!("{}  ", entry.rule), colors.text_primary()),
+                Span::styled(entry.describe(), text_style),
+                Span::styled(
+                    if entry.undone { "  (undone)" } else { "" },
+                    colors.text_muted(),
+                ),
+            ]))
+            .style(base_style)
+        })
+        .collect();
+
+    let mut list_state = ListState::default().with_selected(state.selected_journal);
+    frame.render_stateful_widget(List::new(items).block(block), area, &mut list_state);
+}
+
 fn render_status_bar(frame: &mut Frame, state: &AppState, area: Rect) {
     let colors = state.theme.colors();
 
@@ -633,7 +832,7 @@ fn render_help_popup(frame: &mut Frame, state: &AppState) {
 
     // Calculate popup size
     let popup_width = 60u16.min(area.width.saturating_sub(4));
-    let popup_height = 32u16.min(area.height.saturating_sub(4));
+    let popup_height = 38u16.min(area.height.saturating_sub(4));
 
     let popup_area = Rect {
         x: (area.width - popup_width) / 2,
@@ -688,6 +887,10 @@ fn render_help_popup(frame: &mut Frame, state: &AppState) {
             Span::styled("  d                  ", colors.key_hint()),
             Span::styled("Delete selected rule", colors.text()),
         ]),
+        Line::from(vec![
+            Span::styled("  p                  ", colors.key_hint()),
+            Span::styled("Preview what the rule would do", colors.text()),
+        ]),
         Line::from(""),
         Line::from(vec![Span::styled(
             "  Watches View",
@@ -706,6 +909,19 @@ fn render_help_popup(frame: &mut Frame, state: &AppState) {
             Span::styled("Delete selected watch", colors.text()),
         ]),
         Line::from(""),
+        Line::from(vec![Span::styled(
+            "  Log View",
+            colors.text_primary().add_modifier(Modifier::BOLD),
+        )]),
+        Line::from(vec![
+            Span::styled("  u                  ", colors.key_hint()),
+            Span::styled("Undo selected action", colors.text()),
+        ]),
+        Line::from(vec![
+            Span::styled("  c                  ", colors.key_hint()),
+            Span::styled("Clear activity log", colors.text()),
+        ]),
+        Line::from(""),
         Line::from(vec![Span::styled(
             "  Dashboard",
             colors.text_primary().add_modifier(Modifier::BOLD),
@@ -1092,7 +1308,7 @@ fn render_rule_editor(frame: &mut Frame, state: &AppState) {
                 },
                 field_style(RuleEditorField::SizeGreater),
             ),
-            Span::styled(" bytes", colors.text_dim()),
+            invalid_hint::<ByteSize>(&editor.size_greater, &colors),
         ]),
         Line::from(vec![
             Span::styled(
@@ -1108,14 +1324,14 @@ fn render_rule_editor(frame: &mut Frame, state: &AppState) {
                 },
                 field_style(RuleEditorField::SizeLess),
             ),
-            Span::styled(" bytes", colors.text_dim()),
+            invalid_hint::<ByteSize>(&editor.size_less, &colors),
         ]),
         Line::from(vec![
             Span::styled(
                 format!(" {} ", cursor(RuleEditorField::AgeGreater)),
                 field_style(RuleEditorField::AgeGreater),
             ),
-            Span::styled("Age > days:  ", label_style(RuleEditorField::AgeGreater)),
+            Span::styled("Age >:       ", label_style(RuleEditorField::AgeGreater)),
             Span::styled(
                 if editor.age_greater.is_empty() {
                     "(any)"
@@ -1124,13 +1340,14 @@ fn render_rule_editor(frame: &mut Frame, state: &AppState) {
                 },
                 field_style(RuleEditorField::AgeGreater),
             ),
+            invalid_hint::<Age>(&editor.age_greater, &colors),
         ]),
         Line::from(vec![
             Span::styled(
                 format!(" {} ", cursor(RuleEditorField::AgeLess)),
                 field_style(RuleEditorField::AgeLess),
             ),
-            Span::styled("Age < days:  ", label_style(RuleEditorField::AgeLess)),
+            Span::styled("Age <:       ", label_style(RuleEditorField::AgeLess)),
             Span::styled(
                 if editor.age_less.is_empty() {
                     "(any)"
@@ -1139,6 +1356,7 @@ fn render_rule_editor(frame: &mut Frame, state: &AppState) {
                 },
                 field_style(RuleEditorField::AgeLess),
             ),
+            invalid_hint::<Age>(&editor.age_less, &colors),
         ]),
         Line::from(vec![
             Span::styled(
@@ -1292,6 +1510,15 @@ fn render_rule_editor(frame: &mut Frame, state: &AppState) {
     }
 }
 
+/// Error marker shown after a size or age field that doesn't parse
+fn invalid_hint<T: std::str::FromStr>(value: &str, colors: &ThemeColors) -> Span<'static> {
+    if value.is_empty() || value.parse::<T>().is_ok() {
+        Span::raw("")
+    } else {
+        Span::styled("  invalid", colors.text_error())
+    }
+}
+
 /// Returns contextual help text for each rule editor field
 fn field_help(field: RuleEditorField) -> &'static str {
     use RuleEditorField::*;
@@ -1301,10 +1528,10 @@ fn field_help(field: RuleEditorField) -> &'static str {
         Extension => "e.g. 'pdf', 'jpg' — leave empty for any",
         NameGlob => "Glob pattern, e.g. 'Screenshot*.png' or '*.tmp'",
         NameRegex => "Regex pattern, e.g. '^invoice_\\d+\\.pdf$'",
-        SizeGreater => "Type bytes (e.g. 1048576 = 1MB) — files larger than this",
-        SizeLess => "Type bytes — files smaller than this",
-        AgeGreater => "Type days — files older than this many days",
-        AgeLess => "Type days — files newer than this many days",
+        SizeGreater => "e.g. 25MB, 1.5GiB or plain bytes — files larger than this",
+        SizeLess => "e.g. 500KB, 2GiB or plain bytes — files smaller than this",
+        AgeGreater => "e.g. 90m, 36h, 2w (plain numbers are days) — files older than this",
+        AgeLess => "e.g. 90m, 36h, 2w (plain numbers are days) — files newer than this",
         IsDirectory => "Space/←→ to cycle: Any → Yes → No",
         IsHidden => "Space/←→ to cycle: Any → Yes → No",
         ActionType => "←→ or Space to change action type",
@@ -2,7 +2,7 @@
 
 mod schema;
 
-pub use schema::{Config, WatchConfig};
+pub use schema::{Config, WatchBackend, WatchConfig};
 
 use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
@@ -61,4 +61,48 @@ impl Config {
     pub fn data_dir() -> Option<PathBuf> {
         dirs::data_dir().map(|d| d.join("hazelnut"))
     }
+
+    /// Get the state directory (PID file, logs, trash index)
+    /// Uses ~/.local/state/hazelnut/ on all platforms, like the daemon
+    pub fn state_dir() -> Option<PathBuf> {
+        dirs::home_dir().map(|h| h.join(".local").join("state").join("hazelnut"))
+    }
+}
+
+impl WatchConfig {
+    /// Check if a path falls under this watch (respects `recursive`)
+    pub fn contains(&self, path: &Path) -> bool {
+        let root = crate::expand_path(&self.path);
+        if self.recursive {
+            path != root && path.starts_with(&root)
+        } else {
+            path.parent() == Some(root.as_path())
+        }
+    }
+
+    /// Check if a rule applies to files in this watch
+    pub fn allows_rule(&self, rule_name: &str) -> bool {
+        self.rules.is_empty() || self.rules.iter().any(|r| r == rule_name)
+    }
+}
+
+/// Check if a rule applies to a path given the configured watches
+///
+/// When watches overlap, a rule applies if any watch containing the path allows
+/// it. Paths outside every watch get all rules.
+pub fn rule_applies(watches: &[WatchConfig], path: &Path, rule_name: &str) -> bool {
+    let mut containing = watches.iter().filter(|w| w.contains(path)).peekable();
+    if containing.peek().is_none() {
+        return true;
+    }
+    containing.any(|w| w.allows_rule(rule_name))
+}
+
+/// Root of the innermost watch containing a path
+pub fn watch_root(watches: &[WatchConfig], path: &Path) -> Option<PathBuf> {
+    watches
+        .iter()
+        .filter(|w| w.contains(path))
+        .map(|w| crate::expand_path(&w.path))
+        .max_by_key(|root| root.components().count())
 }
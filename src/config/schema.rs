@@ -1,6 +1,6 @@
 //! Configuration schema
 
-use crate::rules::Rule;
+use crate::rules::{Rule, Schedule};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -35,14 +35,37 @@ pub struct GeneralConfig {
     #[serde(default = "default_debounce")]
     pub debounce_seconds: u64,
 
+    /// Identical size/mtime samples (one second apart) needed before a changed
+    /// file is processed; 0 disables the check, 1 only checks the file is
+    /// still there
+    #[serde(default)]
+    pub stability_samples: u32,
+
     /// Polling interval in seconds for watching file changes
     #[serde(default = "default_polling_interval")]
     pub polling_interval_secs: u64,
 
+    /// Files per second checked by startup scans and rescans
+    #[serde(default = "default_scan_rate")]
+    pub scan_files_per_second: usize,
+
+    /// Times rules may act on the same file within `loop_window_secs` before
+    /// the daemon stops processing it; 0 disables loop detection
+    #[serde(default = "default_loop_limit")]
+    pub loop_limit: usize,
+
+    /// Window in seconds for `loop_limit`
+    #[serde(default = "default_loop_window")]
+    pub loop_window_secs: u64,
+
     /// Maximum number of log entries to retain
     #[serde(default = "default_log_retention")]
     pub log_retention: usize,
 
+    /// Days actions are kept in the undo journal; 0 keeps them forever
+    #[serde(default = "default_journal_retention")]
+    pub journal_retention_days: u64,
+
     /// Start daemon automatically when TUI launches
     #[serde(default)]
     pub start_daemon_on_launch: bool,
@@ -62,8 +85,13 @@ impl Default for GeneralConfig {
             log_level: default_log_level(),
             log_file: None,
             debounce_seconds: default_debounce(),
+            stability_samples: 0,
             polling_interval_secs: default_polling_interval(),
+            scan_files_per_second: default_scan_rate(),
+            loop_limit: default_loop_limit(),
+            loop_window_secs: default_loop_window(),
             log_retention: default_log_retention(),
+            journal_retention_days: default_journal_retention(),
             start_daemon_on_launch: false,
             notifications_enabled: false,
             theme: None,
@@ -83,12 +111,28 @@ fn default_polling_interval() -> u64 {
     5
 }
 
+fn default_scan_rate() -> usize {
+    50
+}
+
+fn default_loop_limit() -> usize {
+    5
+}
+
+fn default_loop_window() -> u64 {
+    60
+}
+
 fn default_log_retention() -> usize {
     1000
 }
 
+fn default_journal_retention() -> u64 {
+    90
+}
+
 /// Configuration for a watched folder
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct WatchConfig {
     /// Path to watch
     pub path: PathBuf,
@@ -100,6 +144,55 @@ pub struct WatchConfig {
     /// Only apply rules with these names (empty = all rules)
     #[serde(default)]
     pub rules: Vec<String>,
+
+    /// Re-check existing files on this schedule (interval or cron expression)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rescan: Option<Schedule>,
+
+    /// Process the files already in the folder when the daemon starts
+    #[serde(default)]
+    pub scan_on_start: bool,
+
+    /// Glob patterns for files and folders to skip (e.g. "node_modules", "*.tmp")
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// Skip files ignored by .gitignore files in the watched folder
+    #[serde(default)]
+    pub respect_gitignore: bool,
+
+    /// Deepest subfolder level watched in a recursive watch (0 = only the root)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+
+    /// How changes are detected
+    #[serde(default)]
+    pub backend: WatchBackend,
+
+    /// Poll backend: detect changes by comparing file contents, not just
+    /// modification times
+    #[serde(default)]
+    pub compare_contents: bool,
+}
+
+/// How a watched folder is monitored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchBackend {
+    /// The operating system's change notifications (inotify, FSEvents, ...)
+    #[default]
+    Native,
+    /// Scan the folder every `polling_interval_secs`; works on network mounts
+    Poll,
+}
+
+impl std::fmt::Display for WatchBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchBackend::Native => write!(f, "native"),
+            WatchBackend::Poll => write!(f, "poll"),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -117,6 +210,7 @@ mod tests {
         assert_eq!(config.watches.len(), 1);
         assert_eq!(config.watches[0].path.to_string_lossy(), "~/Downloads");
         assert!(!config.watches[0].recursive);
+        assert!(!config.watches[0].scan_on_start);
     }
 
     #[test]
@@ -130,6 +224,12 @@ mod tests {
             path = "~/Downloads"
             recursive = true
             rules = ["pdfs", "images"]
+            scan_on_start = true
+
+            [[watch]]
+            path = "/mnt/nas/shared"
+            backend = "poll"
+            compare_contents = true
 
             [[rule]]
             name = "pdfs"
@@ -146,9 +246,153 @@ mod tests {
         let config: Config = toml::from_str(toml).unwrap();
         assert_eq!(config.general.log_level, "debug");
         assert_eq!(config.general.debounce_seconds, 5);
-        assert_eq!(config.watches.len(), 1);
+        assert_eq!(config.watches.len(), 2);
         assert!(config.watches[0].recursive);
+        assert!(config.watches[0].scan_on_start);
+        assert_eq!(config.watches[0].backend, WatchBackend::Native);
+        assert_eq!(config.watches[1].backend, WatchBackend::Poll);
+        assert!(config.watches[1].compare_contents);
         assert_eq!(config.rules.len(), 1);
         assert_eq!(config.rules[0].name, "pdfs");
+        assert_eq!(config.rules[0].actions.len(), 1);
+
+        let saved = toml::to_string_pretty(&config).unwrap();
+        assert!(saved.contains("[rule.action]"));
+        assert!(saved.contains("backend = \"poll\""));
+    }
+
+    #[test]
+    fn test_parse_condition_groups() {
+        let toml = r#"
+            [[rule]]
+            name = "documents"
+
+            [rule.condition]
+            is_hidden = false
+
+            [[rule.condition.any]]
+            extension = "pdf"
+
+            [[rule.condition.any]]
+            extension = "docx"
+
+            [rule.condition.not]
+            name_matches = "draft*"
+
+            [rule.action]
+            type = "nothing"
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        let condition = &config.rules[0].condition;
+        assert_eq!(condition.any.len(), 2);
+        assert_eq!(condition.any[1].extension.as_deref(), Some("docx"));
+        assert_eq!(
+            condition.not.as_ref().unwrap().name_matches.as_deref(),
+            Some("draft*")
+        );
+
+        // Groups survive a save/load round trip
+        let saved = toml::to_string_pretty(&config).unwrap();
+        let reloaded: Config = toml::from_str(&saved).unwrap();
+        assert_eq!(reloaded.rules[0].condition.any.len(), 2);
+        assert!(reloaded.rules[0].condition.not.is_some());
+    }
+
+    #[test]
+    fn test_parse_action_pipeline() {
+        let toml = r#"
+            [[rule]]
+            name = "archive invoices"
+
+            [rule.condition]
+            extension = "pdf"
+
+            [[rule.actions]]
+            type = "rename"
+            pattern = "{date}_{filename}"
+
+            [[rule.actions]]
+            type = "move"
+            destination = "~/Documents/Invoices"
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        let actions = &config.rules[0].actions;
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0].name(), "rename");
+        assert_eq!(actions[1].name(), "move");
+
+        // Pipelines stay a list, single actions keep the `action` table
+        let saved = toml::to_string_pretty(&config).unwrap();
+        assert!(saved.contains("[[rule.actions]]"));
+        let reloaded: Config = toml::from_str(&saved).unwrap();
+        assert_eq!(reloaded.rules[0].actions.len(), 2);
+
+        let both = format!("{}\n[rule.action]\ntype = \"trash\"\n", toml);
+        assert!(toml::from_str::<Config>(&both).is_err());
+        let neither = "[[rule]]\nname = \"empty\"\n";
+        assert!(toml::from_str::<Config>(neither).is_err());
+    }
+
+    #[test]
+    fn test_parse_rescan_schedules() {
+        let toml = r#"
+            [[watch]]
+            path = "~/Desktop"
+            rescan = "0 3 * * *"
+
+            [[rule]]
+            name = "old screenshots"
+            rescan = "1h"
+
+            [rule.condition]
+            age_greater_than = "7d"
+
+            [rule.action]
+            type = "trash"
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        assert!(matches!(config.watches[0].rescan, Some(Schedule::Cron(_))));
+        assert_eq!(config.rules[0].rescan, Some("1h".parse().unwrap()));
+
+        let saved = toml::to_string_pretty(&config).unwrap();
+        assert!(saved.contains("rescan = \"0 3 * * *\""));
+        assert!(saved.contains("rescan = \"1h\""));
+
+        let invalid = toml.replace("\"1h\"", "\"hourly\"");
+        assert!(toml::from_str::<Config>(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_rule_applies_per_watch() {
+        let watch = |path: &str, recursive: bool, rules: &[&str]| WatchConfig {
+            path: PathBuf::from(path),
+            recursive,
+            rules: rules.iter().map(|r| r.to_string()).collect(),
+            ..Default::default()
+        };
+        let watches = vec![
+            watch("/home/u/Downloads", false, &["installers"]),
+            watch("/home/u/Documents", true, &["pdfs"]),
+            watch("/home/u/Documents/Inbox", false, &[]),
+        ];
+        let applies = |path: &str, rule: &str| {
+            crate::config::rule_applies(&watches, std::path::Path::new(path), rule)
+        };
+
+        assert!(applies("/home/u/Downloads/setup.dmg", "installers"));
+        assert!(!applies("/home/u/Documents/setup.dmg", "installers"));
+        assert!(applies("/home/u/Documents/a/b/report.pdf", "pdfs"));
+
+        // Non-recursive watch doesn't cover subfolders
+        assert!(!watches[0].contains(std::path::Path::new("/home/u/Downloads/sub/a.dmg")));
+
+        // Overlapping watches: the Inbox watch allows every rule
+        assert!(applies("/home/u/Documents/Inbox/setup.dmg", "installers"));
+
+        // Outside every watch, all rules apply
+        assert!(applies("/tmp/setup.dmg", "installers"));
     }
 }
@@ -302,6 +302,8 @@ mod unix_daemon {
                     }
                 }
             }
+
+            hazelnut::watcher::print_statuses();
         } else {
             println!("🌰 Hazelnut daemon is not running");
         }
@@ -327,6 +329,21 @@ mod unix_daemon {
         Ok(())
     }
 
+    /// Apply the settings a watcher takes beyond its constructor, at startup
+    /// and on reload
+    fn configure_watcher(watcher: &mut hazelnut::Watcher, config: &hazelnut::Config) {
+        watcher.set_scan_rate(config.general.scan_files_per_second);
+        watcher.set_stability_samples(config.general.stability_samples);
+        watcher.set_loop_limit(
+            config.general.loop_limit,
+            std::time::Duration::from_secs(config.general.loop_window_secs),
+        );
+        watcher.set_status_file(hazelnut::watcher::WatchStatus::default_file());
+        watcher.set_restored_file(
+            hazelnut::journal::journal_path().map(|j| hazelnut::journal::restored_path(&j)),
+        );
+    }
+
     async fn run_daemon(config_path: Option<std::path::PathBuf>) -> Result<()> {
         use tokio::signal::unix::{SignalKind, signal};
         use tokio::time::{Duration, interval};
@@ -358,11 +375,12 @@ mod unix_daemon {
             config.general.polling_interval_secs,
             config.general.debounce_seconds,
         )?;
+        configure_watcher(&mut watcher, &config);
 
         for watch in &config.watches {
             let expanded_path = hazelnut::expand_path(&watch.path);
             info!("Watching: {}", expanded_path.display());
-            if let Err(e) = watcher.watch(&expanded_path, watch.recursive) {
+            if let Err(e) = watcher.watch(watch) {
                 tracing::error!("Failed to watch {}: {}", expanded_path.display(), e);
                 hazelnut::notifications::notify_watch_error(
                     &expanded_path.display().to_string(),
@@ -375,6 +393,10 @@ mod unix_daemon {
 
         // Poll for events periodically
         let mut poll_interval = interval(Duration::from_millis(500));
+        // Retry missing watch roots, start due rescans and work through running scans
+        let mut scan_interval = interval(Duration::from_secs(1));
+        // Drop old journal entries, once at startup and then hourly
+        let mut journal_interval = interval(Duration::from_secs(3600));
 
         loop {
             tokio::select! {
@@ -394,6 +416,7 @@ mod unix_daemon {
                             // Update notification settings
                             hazelnut::notifications::init(config.general.notifications_enabled);
                             // Recreate watcher with new rules, polling interval, and debounce
+                            watcher.flush();
                             let engine = hazelnut::RuleEngine::new(config.rules.clone());
                             match hazelnut::Watcher::new(
                                 engine,
@@ -401,9 +424,10 @@ mod unix_daemon {
                                 config.general.debounce_seconds,
                             ) {
                                 Ok(mut new_watcher) => {
+                                    configure_watcher(&mut new_watcher, &config);
                                     for watch in &config.watches {
                                         let expanded_path = hazelnut::expand_path(&watch.path);
-                                        if let Err(e) = new_watcher.watch(&expanded_path, watch.recursive) {
+                                        if let Err(e) = new_watcher.watch(watch) {
                                             tracing::error!("Failed to watch {}: {}", expanded_path.display(), e);
                                             hazelnut::notifications::notify_watch_error(
                                                 &expanded_path.display().to_string(),
@@ -436,6 +460,21 @@ mod unix_daemon {
                         _ => {}
                     }
                 }
+                _ = journal_interval.tick() => {
+                    hazelnut::journal::prune(config.general.journal_retention_days);
+                }
+                _ = scan_interval.tick() => {
+                    watcher.check_watches();
+                    match watcher.run_scans() {
+                        Ok(count) if count > 0 => {
+                            info!("Scan processed {} files", count);
+                        }
+                        Err(e) => {
+                            tracing::error!("Error running scans: {}", e);
+                        }
+                        _ => {}
+                    }
+                }
             }
         }
 
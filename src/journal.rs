@@ -0,0 +1,619 @@
+//! Journal of the actions rules performed, for `hazelnut undo`
+//!
+//! Every action the daemon or `hazelnut run --apply` executes is appended to
+//! `journal.jsonl` in the state directory. Moves, renames, copies and trashes
+//! can be reversed from it; the other actions are recorded for reference.
+//!
+//! The journal is only ever appended to while the daemon may be writing:
+//! undoing an entry appends an `{"undone": ...}` marker line instead of
+//! rewriting it. Writers hold a lock on `journal.lock`, and [`compact`] drops
+//! old entries by writing a new file and renaming it over the old one.
+//!
+//! Files an undo puts back are listed in `restored.json` next to the journal,
+//! so the daemon doesn't run the same rules on them again right away.
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::warn;
+
+use crate::Config;
+use crate::rules::{Age, ProcessReport};
+
+/// One executed action
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// When the action ran
+    pub at: DateTime<Local>,
+    /// Rule the action belongs to
+    pub rule: String,
+    /// Action type (`move`, `rename`, `copy`, `trash`, ...)
+    pub action: String,
+    /// Path the action ran on
+    pub source: PathBuf,
+    /// Where the file was afterwards (None if it no longer existed)
+    pub result: Option<PathBuf>,
+    /// File or folder the action made (a copy, an archive, the file in the trash)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<PathBuf>,
+    /// Whether the action has been undone
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub undone: bool,
+}
+
+impl JournalEntry {
+    /// Whether this kind of action can be reversed at all
+    pub fn can_undo(&self) -> bool {
+        !self.undone && matches!(self.action.as_str(), "move" | "rename" | "copy" | "trash")
+    }
+
+    /// Short description, e.g. `move ~/Downloads/a.pdf → ~/Documents/a.pdf`
+    pub fn describe(&self) -> String {
+        let target = match self.action.as_str() {
+            "copy" | "trash" => self.created.as_ref(),
+            _ => self.result.as_ref().filter(|r| **r != self.source),
+        };
+        match target {
+            Some(target) => format!(
+                "{} {} → {}",
+                self.action,
+                self.source.display(),
+                target.display()
+            ),
+            None => format!("{} {}", self.action, self.source.display()),
+        }
+    }
+}
+
+/// A line of the journal: an action, or a marker saying one was undone
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JournalLine {
+    Undone { undone: JournalEntry },
+    Entry(JournalEntry),
+}
+
+/// Marker appended when an entry is undone
+#[derive(Serialize)]
+struct UndoneMarker<'a> {
+    undone: &'a JournalEntry,
+}
+
+/// A file an undo put back, which rules leave alone until it changes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestoredFile {
+    /// Where the file was put back
+    pub path: PathBuf,
+    /// Its modification time and size at the time
+    pub modified: SystemTime,
+    pub len: u64,
+}
+
+impl RestoredFile {
+    /// Record a file that is about to be moved from `from` back to `path`
+    fn before_restore(path: &Path, from: &Path) -> Option<Self> {
+        let meta = from.symlink_metadata().ok()?;
+        Some(Self {
+            path: path.to_path_buf(),
+            modified: meta.modified().ok()?,
+            len: meta.len(),
+        })
+    }
+
+    /// Whether the file is still there and hasn't changed since it was put back
+    pub fn is_unchanged(&self) -> bool {
+        Self::before_restore(&self.path, &self.path).as_ref() == Some(self)
+    }
+}
+
+/// The list of restored files kept next to a journal
+pub fn restored_path(journal: &Path) -> PathBuf {
+    journal.with_file_name("restored.json")
+}
+
+/// Read the list of restored files (empty if there is none)
+pub fn load_restored(file: &Path) -> Vec<RestoredFile> {
+    fs::read_to_string(file)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_restored(file: &Path, restored: &[RestoredFile]) -> Result<()> {
+    // The daemon reads this file while undo runs, never let it see it half written
+    let partial = file.with_extension("json.partial");
+    fs::write(&partial, serde_json::to_string_pretty(restored)?)
+        .with_context(|| format!("Failed to write {}", partial.display()))?;
+    fs::rename(&partial, file).with_context(|| format!("Failed to write {}", file.display()))
+}
+
+/// Which journal entries `hazelnut undo` reverses
+#[derive(Debug, Clone, Default)]
+pub struct UndoFilter {
+    /// The last N undoable actions (1 if neither this nor `since` is set)
+    pub last: Option<usize>,
+    /// Every undoable action at or after this time
+    pub since: Option<DateTime<Local>>,
+    /// Only actions of this rule
+    pub rule: Option<String>,
+}
+
+impl UndoFilter {
+    /// The entries to undo, in journal order
+    pub fn select(&self, entries: Vec<JournalEntry>) -> Vec<JournalEntry> {
+        let mut selected: Vec<_> = entries
+            .into_iter()
+            .filter(|e| e.can_undo())
+            .filter(|e| self.rule.as_ref().is_none_or(|rule| e.rule == *rule))
+            .filter(|e| self.since.is_none_or(|since| e.at >= since))
+            .collect();
+        if self.since.is_none() || self.last.is_some() {
+            let last = self.last.unwrap_or(1);
+            selected.drain(..selected.len().saturating_sub(last));
+        }
+        selected
+    }
+}
+
+/// Parse a `--since` time: a date, a date and time, or an age like `2h`
+pub fn parse_time(s: &str) -> Result<DateTime<Local>> {
+    let s = s.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&Local));
+    }
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        });
+    if let Some(naive) = naive {
+        return naive
+            .and_local_timezone(Local)
+            .earliest()
+            .with_context(|| format!("{} doesn't exist in the local time zone", s));
+    }
+
+    let age: Age = s.parse().map_err(|e| {
+        anyhow::anyhow!(
+            "{} (expected e.g. 2024-01-15, \"2024-01-15 14:30\" or 2h)",
+            e
+        )
+    })?;
+    Ok(Local::now() - age.duration())
+}
+
+/// Path of the journal
+pub fn journal_path() -> Option<PathBuf> {
+    Config::state_dir().map(|d| d.join("journal.jsonl"))
+}
+
+/// Append the actions of a processing run to the journal
+///
+/// Failing to write the journal is logged, not returned, so it never stops
+/// rules from running.
+pub fn record_report(report: &ProcessReport) {
+    if report.applied.is_empty() {
+        return;
+    }
+    let Some(journal) = journal_path() else {
+        return;
+    };
+    let now = Local::now();
+    let entries: Vec<_> = report
+        .applied
+        .iter()
+        .map(|applied| JournalEntry {
+            at: now,
+            rule: applied.rule.clone(),
+            action: applied.action.name().to_string(),
+            source: applied.path.clone(),
+            result: applied.result.clone(),
+            created: applied.created.clone(),
+            undone: false,
+        })
+        .collect();
+    if let Err(e) = record(&journal, &entries) {
+        warn!("Failed to write journal {}: {:#}", journal.display(), e);
+    }
+}
+
+/// Append entries to a journal
+pub fn record(journal: &Path, entries: &[JournalEntry]) -> Result<()> {
+    let mut content = String::new();
+    for entry in entries {
+        content.push_str(&serde_json::to_string(entry)?);
+        content.push('\n');
+    }
+    append(journal, &content)
+}
+
+/// Append lines to a journal while holding its lock
+fn append(journal: &Path, content: &str) -> Result<()> {
+    let _lock = lock(journal)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(journal)
+        .with_context(|| format!("Failed to open {}", journal.display()))?;
+    file.write_all(content.as_bytes())
+        .with_context(|| format!("Failed to write {}", journal.display()))
+}
+
+/// Take the journal's write lock, released when the file is dropped
+fn lock(journal: &Path) -> Result<File> {
+    if let Some(parent) = journal.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let path = journal.with_extension("lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    file.lock()
+        .with_context(|| format!("Failed to lock {}", path.display()))?;
+    Ok(file)
+}
+
+/// Drop journal entries older than `keep_days` from the journal in the state
+/// directory (0 keeps everything)
+///
+/// Like [`record_report`], failures are logged rather than returned.
+pub fn prune(keep_days: u64) {
+    let Some(journal) = journal_path() else {
+        return;
+    };
+    if let Err(e) = compact(&journal, keep_days) {
+        warn!("Failed to compact journal {}: {:#}", journal.display(), e);
+    }
+}
+
+/// Drop entries older than `keep_days` from a journal (0 keeps everything)
+///
+/// Undone markers are folded into the entries that are kept. Nothing is
+/// written while no entry is old enough to drop.
+pub fn compact(journal: &Path, keep_days: u64) -> Result<()> {
+    let Some(cutoff) = i64::try_from(keep_days)
+        .ok()
+        .filter(|days| *days > 0)
+        .and_then(chrono::Duration::try_days)
+        .and_then(|keep| Local::now().checked_sub_signed(keep))
+    else {
+        return Ok(());
+    };
+
+    let _lock = lock(journal)?;
+    let entries = list_in(journal)?;
+    if entries.iter().all(|entry| entry.at >= cutoff) {
+        return Ok(());
+    }
+
+    let mut content = String::new();
+    for entry in entries.iter().filter(|entry| entry.at >= cutoff) {
+        content.push_str(&serde_json::to_string(entry)?);
+        content.push('\n');
+    }
+    let partial = journal.with_extension("jsonl.partial");
+    fs::write(&partial, content)
+        .with_context(|| format!("Failed to write {}", partial.display()))?;
+    fs::rename(&partial, journal)
+        .with_context(|| format!("Failed to replace {}", journal.display()))
+}
+
+/// All entries of the journal, oldest first
+pub fn list() -> Result<Vec<JournalEntry>> {
+    match journal_path() {
+        Some(journal) => list_in(&journal),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Read a journal, skipping lines that can't be parsed
+pub fn list_in(journal: &Path) -> Result<Vec<JournalEntry>> {
+    let content = match fs::read_to_string(journal) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read {}", journal.display()));
+        }
+    };
+
+    let mut entries: Vec<JournalEntry> = Vec::new();
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str::<JournalLine>(line) {
+            Ok(JournalLine::Entry(entry)) => entries.push(entry),
+            Ok(JournalLine::Undone { undone }) => {
+                if let Some(entry) = entries.iter_mut().rev().find(|e| **e == undone) {
+                    entry.undone = true;
+                }
+            }
+            Err(e) => warn!("Skipping invalid journal entry: {}", e),
+        }
+    }
+    Ok(entries)
+}
+
+/// Reverse an action on disk
+///
+/// Moves and renames are moved back, copies are removed as long as they still
+/// match the original and trashed files are restored. Nothing is overwritten or
+/// lost: if the original path is taken again, or the copy was edited, the undo
+/// fails.
+pub fn revert(entry: &JournalEntry) -> Result<()> {
+    if entry.undone {
+        bail!("Already undone");
+    }
+    match entry.action.as_str() {
+        "move" | "rename" => {
+            let Some(ref current) = entry.result else {
+                bail!(
+                    "{} was removed, nothing to move back",
+                    entry.source.display()
+                );
+            };
+            if *current == entry.source {
+                return Ok(());
+            }
+            if current.symlink_metadata().is_err() {
+                bail!("{} no longer exists", current.display());
+            }
+            if entry.source.symlink_metadata().is_ok() {
+                bail!("A file already exists at {}", entry.source.display());
+            }
+            if let Some(parent) = entry.source.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+            }
+            crate::rules::move_path(current, &entry.source)
+        }
+        "copy" => {
+            let Some(ref copy) = entry.created else {
+                // Skipped because of a conflict, nothing was copied
+                return Ok(());
+            };
+            if copy.symlink_metadata().is_err() {
+                return Ok(());
+            }
+            // Never delete a copy the user has edited since
+            let original = entry.result.as_ref().unwrap_or(&entry.source);
+            if !crate::rules::same_content(copy, original).unwrap_or(false) {
+                bail!(
+                    "{} no longer matches {}, leaving it in place",
+                    copy.display(),
+                    original.display()
+                );
+            }
+            match fs::remove_file(copy) {
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                result => result.with_context(|| format!("Failed to remove {}", copy.display())),
+            }
+        }
+        "trash" => {
+            let item = crate::trash::list()?
+                .into_iter()
+                .find(|item| Some(item.file_path()) == entry.created)
+                .with_context(|| format!("{} is no longer in the trash", entry.source.display()))?;
+            crate::trash::restore(&item)
+        }
+        other => bail!("{} actions can't be undone", other),
+    }
+}
+
+/// Mark entries of a journal as undone
+pub fn mark_undone(journal: &Path, undone: &[JournalEntry]) -> Result<()> {
+    if undone.is_empty() {
+        return Ok(());
+    }
+    let mut content = String::new();
+    for entry in undone {
+        content.push_str(&serde_json::to_string(&UndoneMarker { undone: entry })?);
+        content.push('\n');
+    }
+    append(journal, &content)
+}
+
+/// Undo entries (given in journal order) newest first, and mark the ones
+/// that worked in the journal
+///
+/// Going backwards puts a file through its pipeline steps in reverse, so a
+/// rename followed by a move is undone as a move back, then a rename back.
+/// Files that are put back are added to the restored list before they move,
+/// so a running daemon leaves them alone. Returns each entry with the result
+/// of undoing it.
+pub fn undo(journal: &Path, entries: Vec<JournalEntry>) -> Result<Vec<(JournalEntry, Result<()>)>> {
+    let restored_file = restored_path(journal);
+    let mut restored = load_restored(&restored_file);
+    restored.retain(RestoredFile::is_unchanged);
+
+    let mut results = Vec::new();
+    let mut done = Vec::new();
+    for entry in entries.into_iter().rev() {
+        let returning = restore_origin(&entry)
+            .and_then(|from| RestoredFile::before_restore(&entry.source, from));
+        if let Some(ref file) = returning {
+            restored.push(file.clone());
+            save_restored(&restored_file, &restored)?;
+        }
+
+        let result = revert(&entry);
+        if result.is_ok() {
+            done.push(entry.clone());
+        } else if let Some(ref file) = returning {
+            restored.retain(|r| r != file);
+        }
+        results.push((entry, result));
+    }
+    save_restored(&restored_file, &restored)?;
+    mark_undone(journal, &done)?;
+    Ok(results)
+}
+
+/// Where the file an undo puts back at the entry's source is now
+fn restore_origin(entry: &JournalEntry) -> Option<&Path> {
+    match entry.action.as_str() {
+        "move" | "rename" => entry.result.as_deref().filter(|r| *r != entry.source),
+        "trash" => entry.created.as_deref(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(rule: &str, action: &str, source: &Path, result: Option<&Path>) -> JournalEntry {
+        JournalEntry {
+            at: Local::now(),
+            rule: rule.to_string(),
+            action: action.to_string(),
+            source: source.to_path_buf(),
+            result: result.map(Path::to_path_buf),
+            created: None,
+            undone: false,
+        }
+    }
+
+    #[test]
+    fn test_undo_pipeline() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let journal = dir.join("journal.jsonl");
+        std::fs::create_dir(dir.join("docs")).unwrap();
+
+        // "a.pdf" was renamed to "2024_a.pdf", moved into docs/, then copied
+        let renamed = dir.join("2024_a.pdf");
+        let moved = dir.join("docs/2024_a.pdf");
+        let copy = dir.join("backup.pdf");
+        std::fs::write(&moved, "pdf").unwrap();
+        std::fs::write(&copy, "pdf").unwrap();
+        let mut copied = entry("file", "copy", &moved, Some(&moved));
+        copied.created = Some(copy.clone());
+        let entries = vec![
+            entry("date", "rename", &dir.join("a.pdf"), Some(&renamed)),
+            entry("file", "move", &renamed, Some(&moved)),
+            copied,
+            entry("file", "delete", &dir.join("old.tmp"), None),
+        ];
+        record(&journal, &entries).unwrap();
+
+        let filter = UndoFilter {
+            rule: Some("file".into()),
+            last: Some(5),
+            ..Default::default()
+        };
+        let selected = filter.select(list_in(&journal).unwrap());
+        assert_eq!(selected.len(), 2);
+
+        // Undo everything; the delete can't be undone and isn't selected
+        let all = UndoFilter {
+            last: Some(10),
+            ..Default::default()
+        };
+        let results = undo(&journal, all.select(list_in(&journal).unwrap())).unwrap();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+        assert_eq!(std::fs::read_to_string(dir.join("a.pdf")).unwrap(), "pdf");
+        assert!(!moved.exists());
+        assert!(!copy.exists());
+
+        // Undone entries are marked and not selected again
+        let after = list_in(&journal).unwrap();
+        assert_eq!(after.iter().filter(|e| e.undone).count(), 3);
+        assert!(all.select(after).is_empty());
+
+        // The files put back are listed for the daemon until they change
+        let restored = load_restored(&restored_path(&journal));
+        let paths: Vec<_> = restored.iter().map(|r| r.path.clone()).collect();
+        assert_eq!(paths, [renamed.clone(), dir.join("a.pdf")]);
+        let original = &restored[1];
+        assert!(original.is_unchanged());
+        std::fs::write(dir.join("a.pdf"), "edited").unwrap();
+        assert!(!original.is_unchanged());
+    }
+
+    #[test]
+    fn test_undone_markers_and_compaction() {
+        let temp = tempfile::tempdir().unwrap();
+        let journal = temp.path().join("journal.jsonl");
+        let mut entries: Vec<_> = (0..5)
+            .map(|i| {
+                let source = temp.path().join(format!("{}.txt", i));
+                entry("file", "copy", &source, Some(&source))
+            })
+            .collect();
+        for (old, entry) in entries.iter_mut().take(2).enumerate() {
+            entry.at = Local::now() - chrono::Duration::days(100 - old as i64);
+        }
+        record(&journal, &entries).unwrap();
+
+        // Undoing appends a marker instead of rewriting the entry
+        mark_undone(&journal, &entries[3..4]).unwrap();
+        let lines = std::fs::read_to_string(&journal).unwrap();
+        assert_eq!(lines.lines().count(), 6);
+        assert!(lines.lines().next().unwrap().contains("0.txt"));
+        let listed = list_in(&journal).unwrap();
+        assert_eq!(listed.len(), 5);
+        assert!(listed[3].undone);
+
+        // Compacting drops old entries and folds the markers in
+        compact(&journal, 0).unwrap();
+        compact(&journal, 365).unwrap();
+        assert_eq!(std::fs::read_to_string(&journal).unwrap(), lines);
+        compact(&journal, 90).unwrap();
+        let kept = list_in(&journal).unwrap();
+        let sources: Vec<_> = kept.iter().map(|e| e.source.clone()).collect();
+        assert_eq!(
+            sources,
+            [2, 3, 4].map(|i| temp.path().join(format!("{}.txt", i)))
+        );
+        assert!(kept[1].undone);
+        assert_eq!(
+            std::fs::read_to_string(&journal).unwrap().lines().count(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_undo_never_overwrites() {
+        let temp = tempfile::tempdir().unwrap();
+        let source = temp.path().join("a.txt");
+        let moved = temp.path().join("b.txt");
+        std::fs::write(&source, "new").unwrap();
+        std::fs::write(&moved, "old").unwrap();
+
+        assert!(revert(&entry("r", "move", &source, Some(&moved))).is_err());
+        assert_eq!(std::fs::read_to_string(&source).unwrap(), "new");
+        assert!(revert(&entry("r", "run", &source, Some(&source))).is_err());
+
+        // A copy edited after it was made is kept
+        let copy = temp.path().join("copy.txt");
+        std::fs::write(&copy, "new, edited").unwrap();
+        let mut copied = entry("r", "copy", &source, Some(&source));
+        copied.created = Some(copy.clone());
+        assert!(revert(&copied).is_err());
+        assert_eq!(std::fs::read_to_string(&copy).unwrap(), "new, edited");
+        std::fs::write(&copy, "new").unwrap();
+        revert(&copied).unwrap();
+        assert!(!copy.exists());
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let day = parse_time("2024-01-15").unwrap();
+        assert_eq!(day.format("%Y-%m-%d %H:%M").to_string(), "2024-01-15 00:00");
+        let time = parse_time("2024-01-15 14:30").unwrap();
+        assert_eq!(time.format("%H:%M").to_string(), "14:30");
+
+        let ago = Local::now() - parse_time("2h").unwrap();
+        assert!((ago.num_minutes() - 120).abs() <= 1);
+        assert!(parse_time("yesterday-ish").is_err());
+    }
+}
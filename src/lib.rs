@@ -7,9 +7,11 @@ pub mod app;
 pub mod autostart;
 pub mod config;
 pub mod ipc;
+pub mod journal;
 pub mod notifications;
 pub mod rules;
 pub mod theme;
+pub mod trash;
 pub mod watcher;
 
 pub use config::Config;
@@ -4,6 +4,8 @@
 
 use anyhow::Result;
 use clap::Parser;
+use hazelnut::config::rule_applies;
+use hazelnut::rules::MatchContext;
 use std::path::PathBuf;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
@@ -44,6 +46,10 @@ enum Commands {
         #[arg(long)]
         apply: bool,
 
+        /// Print the dry-run plan as JSON
+        #[arg(long, conflicts_with = "apply")]
+        json: bool,
+
         /// Target directory to process
         #[arg(short, long)]
         dir: Option<PathBuf>,
@@ -52,10 +58,47 @@ enum Commands {
     /// Show daemon status
     Status,
 
+    /// List or restore files hazelnut moved to the trash
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommands,
+    },
+
+    /// Undo actions rules performed (the most recent one by default)
+    Undo {
+        /// Undo the last N actions
+        #[arg(long, value_name = "N")]
+        last: Option<usize>,
+
+        /// Undo every action since TIME (e.g. 2024-01-15, "2024-01-15 14:30" or 2h)
+        #[arg(long, value_name = "TIME")]
+        since: Option<String>,
+
+        /// Only undo actions of this rule
+        #[arg(long, value_name = "NAME")]
+        rule: Option<String>,
+
+        /// Show what would be undone without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
     /// Check for updates and install if available
     Update,
 }
 
+#[derive(clap::Subcommand, Debug)]
+enum TrashCommands {
+    /// List trashed files that can be restored
+    List,
+
+    /// Restore a trashed file to its original location
+    Restore {
+        /// Number shown by `hazelnut trash list`, or the original path
+        item: String,
+    },
+}
+
 /// Show daemon status
 #[cfg(unix)]
 fn show_daemon_status() {
@@ -116,6 +159,8 @@ fn show_daemon_status() {
                 }
             }
         }
+
+        hazelnut::watcher::print_statuses();
     } else {
         println!("🌰 Hazelnut daemon is not running");
     }
@@ -131,7 +176,12 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     // Initialize logging
-    let log_level = if cli.verbose { "debug" } else { "info" };
+    // Keep JSON output parseable
+    let log_level = match cli.command {
+        _ if cli.verbose => "debug",
+        Some(Commands::Run { json: true, .. }) => "warn",
+        _ => "info",
+    };
     tracing_subscriber::registry()
         .with(tracing_subscriber::EnvFilter::new(
             std::env::var("HAZELNUT_LOG").unwrap_or_else(|_| log_level.to_string()),
@@ -167,37 +217,97 @@ async fn main() -> Result<()> {
                 }
             }
         }
-        Some(Commands::Run { apply, dir }) => {
+        Some(Commands::Run { apply, json, dir }) => {
             let config = hazelnut::Config::load(cli.config.as_deref())?;
-            let engine = hazelnut::RuleEngine::new(config.rules);
+            let engine = hazelnut::RuleEngine::new(config.rules.clone());
+            let watches = &config.watches;
+            let added = hazelnut::watcher::AddedIndex::load_default();
 
             let dirs: Vec<_> = if let Some(d) = dir {
-                vec![d]
+                vec![hazelnut::expand_path(&d)]
             } else {
-                config.watches.iter().map(|w| w.path.clone()).collect()
+                watches
+                    .iter()
+                    .map(|w| hazelnut::expand_path(&w.path))
+                    .collect()
             };
 
+            let mut all_plans = Vec::new();
             for dir in dirs {
-                println!("Processing: {}", dir.display());
+                if !json {
+                    println!("Processing: {}", dir.display());
+                }
+                let mut plans = Vec::new();
                 let entries = std::fs::read_dir(&dir)?;
                 for entry in entries.flatten() {
                     let path = entry.path();
-                    if path.is_file()
-                        && let Some(action) = engine.evaluate(&path)?
-                    {
-                        if apply {
-                            println!("  Applying: {} -> {:?}", path.display(), action);
-                            action.execute(&path)?;
-                        } else {
-                            println!("  [dry-run] {} -> {:?}", path.display(), action);
+                    if !path.is_file() {
+                        continue;
+                    }
+
+                    let base = MatchContext::with_root(Some(&dir)).with_added(added.get(&path));
+                    if apply {
+                        let report = engine.process_with(&path, &base, |rule| {
+                            rule_applies(watches, &path, &rule.name)
+                        })?;
+                        hazelnut::journal::record_report(&report);
+                        for applied in &report.applied {
+                            println!(
+                                "  Applied '{}': {} -> {:?}",
+                                applied.rule,
+                                applied.path.display(),
+                                applied.action
+                            );
+                        }
+                        if let Some(failure) = report.failure {
+                            eprintln!("  ✗ {}", failure);
+                        }
+                    } else {
+                        let plan = engine.plan_with(&path, &base, |rule| {
+                            rule_applies(watches, &path, &rule.name)
+                        })?;
+                        if !plan.is_empty() {
+                            plans.push(plan);
                         }
                     }
                 }
+
+                if json {
+                    all_plans.extend(plans);
+                } else if !apply {
+                    print_plan_table(&dir, &plans);
+                }
+            }
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&all_plans)?);
+            }
+            if apply {
+                hazelnut::journal::prune(config.general.journal_retention_days);
             }
         }
         Some(Commands::Status) => {
             show_daemon_status();
         }
+        Some(Commands::Trash { command }) => {
+            run_trash_command(command)?;
+        }
+        Some(Commands::Undo {
+            last,
+            since,
+            rule,
+            dry_run,
+        }) => {
+            let filter = hazelnut::journal::UndoFilter {
+                last,
+                since: since
+                    .as_deref()
+                    .map(hazelnut::journal::parse_time)
+                    .transpose()?,
+                rule,
+            };
+            run_undo_command(&filter, dry_run)?;
+        }
         Some(Commands::Update) => {
             run_update_command();
         }
@@ -206,6 +316,163 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Print dry-run plans as a table, one row per step
+fn print_plan_table(dir: &std::path::Path, plans: &[hazelnut::rules::FilePlan]) {
+    if plans.is_empty() {
+        println!("  No rules would run");
+        return;
+    }
+
+    let mut rows = vec![[
+        "FILE".to_string(),
+        "RULE".to_string(),
+        "ACTION".to_string(),
+        "TARGET".to_string(),
+        "NOTES".to_string(),
+    ]];
+    // Failures print under the last row of their file
+    let mut failures = Vec::new();
+    for plan in plans {
+        let name = plan
+            .path
+            .strip_prefix(dir)
+            .unwrap_or(&plan.path)
+            .display()
+            .to_string();
+        let mut previous_rule = None;
+        for (i, step) in plan.steps.iter().enumerate() {
+            let rule = if previous_rule == Some(&step.rule) {
+                String::new()
+            } else {
+                step.rule.clone()
+            };
+            previous_rule = Some(&step.rule);
+            rows.push([
+                if i == 0 { name.clone() } else { String::new() },
+                rule,
+                step.action.to_string(),
+                step.target(),
+                step.notes(),
+            ]);
+        }
+        if let Some(ref failure) = plan.failure {
+            if plan.steps.is_empty() {
+                rows.push([
+                    name,
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                ]);
+            }
+            failures.push((rows.len() - 1, failure));
+        }
+    }
+
+    let mut widths = [0; 4];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let mut line = String::from(" ");
+        for (width, cell) in widths.iter().zip(row) {
+            line.push_str(&format!(" {:<width$} ", cell, width = *width));
+        }
+        line.push_str(&row[4]);
+        println!("{}", line.trim_end());
+        for (_, failure) in failures.iter().filter(|(row, _)| *row == i) {
+            println!("    ✗ {}", failure);
+        }
+    }
+}
+
+/// Run the trash subcommands
+fn run_trash_command(command: TrashCommands) -> Result<()> {
+    let items = hazelnut::trash::list()?;
+
+    match command {
+        TrashCommands::List => {
+            if items.is_empty() {
+                println!("🗑 No trashed files");
+                return Ok(());
+            }
+            println!("Trashed files:");
+            for (i, item) in items.iter().enumerate() {
+                println!(
+                    "  [{}] {}  {}",
+                    i + 1,
+                    item.deleted_at.format("%Y-%m-%d %H:%M"),
+                    item.original.display()
+                );
+            }
+        }
+        TrashCommands::Restore { item } => {
+            // Prefer the list number, then the most recently trashed file with that path
+            let found = match item.parse::<usize>() {
+                Ok(n) if n >= 1 && n <= items.len() => items.get(n - 1),
+                _ => {
+                    let path = hazelnut::expand_path(std::path::Path::new(&item));
+                    let path = std::path::absolute(&path).unwrap_or(path);
+                    items.iter().rev().find(|i| i.original == path)
+                }
+            };
+
+            let Some(found) = found else {
+                eprintln!("✗ No trashed file matches '{}'", item);
+                eprintln!("  Run `hazelnut trash list` to see restorable files.");
+                std::process::exit(1);
+            };
+
+            hazelnut::trash::restore(found)?;
+            println!("✓ Restored {}", found.original.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Run the undo command
+fn run_undo_command(filter: &hazelnut::journal::UndoFilter, dry_run: bool) -> Result<()> {
+    let Some(journal) = hazelnut::journal::journal_path() else {
+        anyhow::bail!("Could not determine the state directory");
+    };
+    let entries = filter.select(hazelnut::journal::list_in(&journal)?);
+    if entries.is_empty() {
+        println!("↩ Nothing to undo");
+        return Ok(());
+    }
+
+    if dry_run {
+        println!("Would undo (newest first):");
+        for entry in entries.iter().rev() {
+            println!(
+                "  {}  '{}': {}",
+                entry.at.format("%Y-%m-%d %H:%M"),
+                entry.rule,
+                entry.describe()
+            );
+        }
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for (entry, result) in hazelnut::journal::undo(&journal, entries)? {
+        match result {
+            Ok(()) => println!("  ✓ Undid '{}': {}", entry.rule, entry.describe()),
+            Err(e) => {
+                failed += 1;
+                eprintln!("  ✗ '{}': {}: {:#}", entry.rule, entry.describe(), e);
+            }
+        }
+    }
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
 /// Run the update command
 fn run_update_command() {
     use hazelnut::{
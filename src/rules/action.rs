@@ -5,6 +5,13 @@ use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use tracing::{debug, info};
 
+use super::MatchContext;
+use super::archive::{self, ArchiveFormat};
+use super::conflict::{self, ConflictStrategy, Resolution};
+use super::pattern::{Expansion, expand_command, expand_pattern, expand_unique};
+use super::plan::{ConflictOutcome, PlannedConflict, Prediction};
+use super::transfer;
+
 /// Action to perform on a matched file
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
@@ -15,9 +22,15 @@ pub enum Action {
         /// Create destination if it doesn't exist
         #[serde(default = "default_true")]
         create_destination: bool,
-        /// Overwrite if file exists
+        /// Overwrite if file exists (shorthand for `conflict = "overwrite"`)
         #[serde(default)]
         overwrite: bool,
+        /// What to do if the destination file already exists
+        #[serde(default)]
+        conflict: ConflictStrategy,
+        /// Name pattern for `rename`/`deduplicate` (default: "{name} ({counter}).{ext}")
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        conflict_pattern: Option<String>,
     },
 
     /// Copy file to a destination folder
@@ -27,12 +40,20 @@ pub enum Action {
         create_destination: bool,
         #[serde(default)]
         overwrite: bool,
+        #[serde(default)]
+        conflict: ConflictStrategy,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        conflict_pattern: Option<String>,
     },
 
     /// Rename the file
     Rename {
         /// New name pattern (supports {name}, {ext}, {date}, etc.)
         pattern: String,
+        #[serde(default)]
+        conflict: ConflictStrategy,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        conflict_pattern: Option<String>,
     },
 
     /// Move to trash
@@ -49,13 +70,33 @@ pub enum Action {
         args: Vec<String>,
     },
 
-    /// Archive the file (zip)
+    /// Archive the file or directory
     Archive {
         /// Destination for the archive
         destination: Option<PathBuf>,
-        /// Delete original after archiving
+        /// Delete original after the archive is written and verified
         #[serde(default)]
         delete_original: bool,
+        /// Archive format (zip, tar.gz, tar.zst)
+        #[serde(default)]
+        format: ArchiveFormat,
+        /// Compression level (zip/tar.gz: 0-9, tar.zst: 1-22)
+        #[serde(default)]
+        level: Option<i32>,
+    },
+
+    /// Extract a zip or tar archive
+    Extract {
+        /// Destination folder pattern, relative to the archive's folder
+        /// (default: "{name}", the archive name without its extension)
+        #[serde(default)]
+        destination: Option<PathBuf>,
+        /// Maximum total size of extracted files in bytes (default: 10 GiB)
+        #[serde(default)]
+        max_size: Option<u64>,
+        /// Delete the archive after a successful extraction
+        #[serde(default)]
+        delete_archive: bool,
     },
 
     /// Do nothing (useful for testing conditions)
@@ -66,89 +107,184 @@ fn default_true() -> bool {
     true
 }
 
+/// What running an action did to a file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActionOutput {
+    /// Where the file is afterwards (None if it no longer exists)
+    pub path: Option<PathBuf>,
+
+    /// A file or folder the action made besides it (a copy, an archive, an
+    /// extracted folder, the file in the trash)
+    pub created: Option<PathBuf>,
+}
+
+impl ActionOutput {
+    /// The file is at `path` and nothing else was created
+    fn at(path: &Path) -> Self {
+        Self {
+            path: Some(path.to_path_buf()),
+            created: None,
+        }
+    }
+
+    /// The file no longer exists
+    fn gone() -> Self {
+        Self::default()
+    }
+
+    fn with_created(self, created: PathBuf) -> Self {
+        Self {
+            created: Some(created),
+            ..self
+        }
+    }
+}
+
 impl Action {
+    /// Config name of the action type
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Move { .. } => "move",
+            Action::Copy { .. } => "copy",
+            Action::Rename { .. } => "rename",
+            Action::Trash => "trash",
+            Action::Delete => "delete",
+            Action::Run { .. } => "run",
+            Action::Archive { .. } => "archive",
+            Action::Extract { .. } => "extract",
+            Action::Nothing => "nothing",
+        }
+    }
+
     /// Execute this action on a file
-    pub fn execute(&self, path: &Path) -> Result<()> {
+    ///
+    /// Returns the file's location after the action, or `None` if it no longer
+    /// exists (deleted, trashed, archived with `delete_original`).
+    pub fn execute(&self, path: &Path) -> Result<Option<PathBuf>> {
+        self.execute_with(path, &MatchContext::default())
+    }
+
+    /// Execute this action with values from the rule match (e.g. regex captures)
+    pub fn execute_with(&self, path: &Path, ctx: &MatchContext) -> Result<Option<PathBuf>> {
+        Ok(self.perform_with(path, ctx)?.path)
+    }
+
+    /// Like [`Action::execute_with`], but also reports what the action created
+    pub fn perform_with(&self, path: &Path, ctx: &MatchContext) -> Result<ActionOutput> {
         match self {
             Action::Move {
                 destination,
                 create_destination,
                 overwrite,
+                conflict,
+                conflict_pattern,
             } => {
-                let dest = expand_path(destination);
+                let target = expand_destination(destination, path, ctx)?;
+                let dest = target.parent().unwrap_or(Path::new("."));
 
                 if *create_destination {
-                    std::fs::create_dir_all(&dest).with_context(|| {
+                    std::fs::create_dir_all(dest).with_context(|| {
                         format!("Failed to create directory: {}", dest.display())
                     })?;
                 }
 
-                let filename = path.file_name().context("File has no name")?;
-                let dest_path = dest.join(filename);
-
-                if dest_path.exists() && !overwrite {
-                    anyhow::bail!(
-                        "Destination exists and overwrite is false: {}",
-                        dest_path.display()
-                    );
-                }
+                let dest_path = match conflict::resolve(
+                    conflict.effective(*overwrite),
+                    conflict_pattern.as_deref(),
+                    path,
+                    &target,
+                )? {
+                    Resolution::Proceed(p) => p,
+                    Resolution::Overwrite(p) => {
+                        clear_for_overwrite(&p)?;
+                        p
+                    }
+                    Resolution::Skip => return Ok(ActionOutput::at(path)),
+                    Resolution::Duplicate => {
+                        info!("Removing duplicate {}", path.display());
+                        std::fs::remove_file(path)?;
+                        return Ok(ActionOutput::gone());
+                    }
+                };
 
                 info!("Moving {} -> {}", path.display(), dest_path.display());
-                std::fs::rename(path, &dest_path).with_context(|| {
-                    format!(
-                        "Failed to move {} to {}",
-                        path.display(),
-                        dest_path.display()
-                    )
-                })?;
+                transfer::move_path(path, &dest_path)?;
+
+                Ok(ActionOutput::at(&dest_path))
             }
 
             Action::Copy {
                 destination,
                 create_destination,
                 overwrite,
+                conflict,
+                conflict_pattern,
             } => {
-                let dest = expand_path(destination);
+                let target = expand_destination(destination, path, ctx)?;
+                let dest = target.parent().unwrap_or(Path::new("."));
 
                 if *create_destination {
-                    std::fs::create_dir_all(&dest)?;
+                    std::fs::create_dir_all(dest)?;
                 }
 
-                let filename = path.file_name().context("File has no name")?;
-                let dest_path = dest.join(filename);
-
-                if dest_path.exists() && !overwrite {
-                    anyhow::bail!(
-                        "Destination exists and overwrite is false: {}",
-                        dest_path.display()
-                    );
-                }
+                let dest_path = match conflict::resolve(
+                    conflict.effective(*overwrite),
+                    conflict_pattern.as_deref(),
+                    path,
+                    &target,
+                )? {
+                    Resolution::Proceed(p) => p,
+                    Resolution::Overwrite(p) => {
+                        clear_for_overwrite(&p)?;
+                        p
+                    }
+                    // Nothing to copy, the original stays in place either way
+                    Resolution::Skip | Resolution::Duplicate => {
+                        return Ok(ActionOutput::at(path));
+                    }
+                };
 
                 info!("Copying {} -> {}", path.display(), dest_path.display());
                 std::fs::copy(path, &dest_path)?;
+
+                Ok(ActionOutput::at(path).with_created(dest_path))
             }
 
-            Action::Rename { pattern } => {
-                let new_name = expand_pattern(pattern, path)?;
-                let new_path = path.parent().unwrap_or(Path::new(".")).join(&new_name);
+            Action::Rename {
+                pattern,
+                conflict,
+                conflict_pattern,
+            } => {
+                let parent = path.parent().unwrap_or(Path::new("."));
+                let target = expand_unique(pattern, path, ctx, |name| parent.join(name))?;
+                let new_path =
+                    match conflict::resolve(*conflict, conflict_pattern.as_deref(), path, &target)?
+                    {
+                        Resolution::Proceed(p) => p,
+                        Resolution::Overwrite(p) => {
+                            clear_for_overwrite(&p)?;
+                            p
+                        }
+                        Resolution::Skip => return Ok(ActionOutput::at(path)),
+                        Resolution::Duplicate => {
+                            info!("Removing duplicate {}", path.display());
+                            std::fs::remove_file(path)?;
+                            return Ok(ActionOutput::gone());
+                        }
+                    };
 
                 info!("Renaming {} -> {}", path.display(), new_path.display());
                 std::fs::rename(path, &new_path)?;
+
+                Ok(ActionOutput::at(&new_path))
             }
 
             Action::Trash => {
                 info!("Trashing {}", path.display());
-                // Use trash crate if available, otherwise move to ~/.local/share/Trash
-                // For now, just move to a trash folder
-                let trash_dir = dirs::data_dir()
-                    .map(|d| d.join("Trash").join("files"))
-                    .unwrap_or_else(|| PathBuf::from("~/.local/share/Trash/files"));
-
-                std::fs::create_dir_all(&trash_dir)?;
+                let item = crate::trash::trash(path)?;
+                debug!("Trashed to {}", item.file_path().display());
 
-                let filename = path.file_name().context("File has no name")?;
-                let trash_path = trash_dir.join(filename);
-                std::fs::rename(path, trash_path)?;
+                Ok(ActionOutput::gone().with_created(item.file_path()))
             }
 
             Action::Delete => {
@@ -158,18 +294,13 @@ impl Action {
                 } else {
                     std::fs::remove_file(path)?;
                 }
+
+                Ok(ActionOutput::gone())
             }
 
             Action::Run { command, args } => {
-                // Check if command contains shell operators - if so, run through shell
-                let has_shell_operators = command.contains("&&")
-                    || command.contains("||")
-                    || command.contains(';')
-                    || command.contains('|')
-                    || command.contains('>')
-                    || command.contains('<');
-
-                if has_shell_operators && args.is_empty() {
+                // Commands with shell operators run through the shell
+                if has_shell_operators(command) && args.is_empty() {
                     // Run through shell
                     let shell = if cfg!(target_os = "windows") {
                         "cmd"
@@ -183,8 +314,7 @@ impl Action {
                     };
 
                     // Expand {path} patterns in the command
-                    let expanded_command =
-                        expand_pattern(command, path).unwrap_or_else(|_| command.clone());
+                    let expanded_command = expand_command(command, path, ctx, Expansion::Shell)?;
 
                     info!("Running (shell): {}", expanded_command);
 
@@ -214,10 +344,9 @@ impl Action {
 
                     let mut expanded_args: Vec<String> =
                         base_args.iter().map(|s| s.to_string()).collect();
-                    expanded_args.extend(
-                        args.iter()
-                            .map(|a| expand_pattern(a, path).unwrap_or_else(|_| a.clone())),
-                    );
+                    for arg in args {
+                        expanded_args.push(expand_command(arg, path, ctx, Expansion::Argument)?);
+                    }
 
                     info!("Running: {} {:?}", actual_command, expanded_args);
 
@@ -232,87 +361,357 @@ impl Action {
                         anyhow::bail!("Command failed with status: {}", status);
                     }
                 }
+
+                // The command may have moved or removed the file
+                Ok(ActionOutput {
+                    path: path.exists().then(|| path.to_path_buf()),
+                    created: None,
+                })
             }
 
             Action::Archive {
                 destination,
                 delete_original,
+                format,
+                level,
             } => {
-                let dest = destination
-                    .as_ref()
-                    .map(|p| expand_path(p))
-                    .unwrap_or_else(|| path.parent().unwrap_or(Path::new(".")).to_path_buf());
+                let archive_path = archive_target(destination.as_deref(), *format, path, ctx)?;
+                let dest = archive_path.parent().unwrap_or(Path::new("."));
 
-                let filename = path.file_stem().context("File has no name")?;
-                let archive_name = format!("{}.zip", filename.to_string_lossy());
-                let archive_path = dest.join(&archive_name);
+                std::fs::create_dir_all(dest)
+                    .with_context(|| format!("Failed to create directory: {}", dest.display()))?;
 
-                info!("Archiving {} -> {}", path.display(), archive_path.display());
+                if archive_path.exists() {
+                    anyhow::bail!("Archive already exists: {}", archive_path.display());
+                }
 
-                // TODO: Implement actual zip creation
-                debug!("Archive creation not yet implemented");
+                info!("Archiving {} -> {}", path.display(), archive_path.display());
+                archive::create_archive(path, &archive_path, *format, *level)
+                    .with_context(|| format!("Failed to archive {}", path.display()))?;
 
                 if *delete_original {
+                    info!("Removing original after archiving: {}", path.display());
+                    if path.is_dir() {
+                        std::fs::remove_dir_all(path)?;
+                    } else {
+                        std::fs::remove_file(path)?;
+                    }
+                    return Ok(ActionOutput::gone().with_created(archive_path));
+                }
+
+                Ok(ActionOutput::at(path).with_created(archive_path))
+            }
+
+            Action::Extract {
+                destination,
+                max_size,
+                delete_archive,
+            } => {
+                let dest = extract_target(destination.as_deref(), path, ctx)?;
+
+                info!("Extracting {} -> {}", path.display(), dest.display());
+                archive::extract_archive(
+                    path,
+                    &dest,
+                    max_size.unwrap_or(archive::DEFAULT_MAX_EXTRACT_SIZE),
+                )
+                .with_context(|| format!("Failed to extract {}", path.display()))?;
+
+                if *delete_archive {
+                    info!("Removing archive after extraction: {}", path.display());
                     std::fs::remove_file(path)?;
+                    return Ok(ActionOutput::gone().with_created(dest));
                 }
+
+                Ok(ActionOutput::at(path).with_created(dest))
             }
 
             Action::Nothing => {
                 debug!("No action for {}", path.display());
+                Ok(ActionOutput::at(path))
             }
         }
+    }
+
+    /// Predict what running this action on a file would do, without touching it
+    ///
+    /// Only reads the filesystem: destinations are expanded and checked for
+    /// conflicts, but no folder is created and nothing is moved. `path` may be
+    /// where an earlier step would have put the file; `ctx.data` then says where it
+    /// really is.
+    pub(crate) fn plan_with(&self, path: &Path, ctx: &MatchContext) -> Result<Prediction> {
+        let mut prediction = Prediction::default();
+
+        prediction.output = match self {
+            Action::Move {
+                destination,
+                create_destination,
+                overwrite,
+                conflict,
+                conflict_pattern,
+            } => {
+                let target = expand_destination(destination, path, ctx)?;
+                warn_missing_folder(&target, *create_destination, &mut prediction);
+
+                match plan_conflict(
+                    conflict.effective(*overwrite),
+                    conflict_pattern.as_deref(),
+                    path,
+                    ctx,
+                    target,
+                    &mut prediction,
+                )? {
+                    Resolution::Proceed(p) | Resolution::Overwrite(p) => {
+                        if transfer::crosses_filesystems(path, &p) {
+                            prediction.warnings.push(
+                                "Crosses filesystems: copied, then the original removed"
+                                    .to_string(),
+                            );
+                        }
+                        ActionOutput::at(&p)
+                    }
+                    Resolution::Skip => ActionOutput::at(path),
+                    Resolution::Duplicate => ActionOutput::gone(),
+                }
+            }
+
+            Action::Copy {
+                destination,
+                create_destination,
+                overwrite,
+                conflict,
+                conflict_pattern,
+            } => {
+                let target = expand_destination(destination, path, ctx)?;
+                warn_missing_folder(&target, *create_destination, &mut prediction);
+
+                match plan_conflict(
+                    conflict.effective(*overwrite),
+                    conflict_pattern.as_deref(),
+                    path,
+                    ctx,
+                    target,
+                    &mut prediction,
+                )? {
+                    Resolution::Proceed(p) | Resolution::Overwrite(p) => {
+                        ActionOutput::at(path).with_created(p)
+                    }
+                    Resolution::Skip | Resolution::Duplicate => ActionOutput::at(path),
+                }
+            }
+
+            Action::Rename {
+                pattern,
+                conflict,
+                conflict_pattern,
+            } => {
+                let parent = path.parent().unwrap_or(Path::new("."));
+                let target = expand_unique(pattern, path, ctx, |name| parent.join(name))?;
+
+                match plan_conflict(
+                    *conflict,
+                    conflict_pattern.as_deref(),
+                    path,
+                    ctx,
+                    target,
+                    &mut prediction,
+                )? {
+                    Resolution::Proceed(p) | Resolution::Overwrite(p) => ActionOutput::at(&p),
+                    Resolution::Skip => ActionOutput::at(path),
+                    Resolution::Duplicate => ActionOutput::gone(),
+                }
+            }
+
+            Action::Trash => ActionOutput::gone(),
+
+            Action::Delete => {
+                prediction
+                    .warnings
+                    .push("Deleted permanently, can't be undone".to_string());
+                ActionOutput::gone()
+            }
+
+            Action::Run { command, args } => {
+                prediction.command = Some(command_line(command, args, path, ctx)?);
+                ActionOutput::at(path)
+            }
 
-        Ok(())
+            Action::Archive {
+                destination,
+                delete_original,
+                format,
+                ..
+            } => {
+                let archive_path = archive_target(destination.as_deref(), *format, path, ctx)?;
+                if archive_path.exists() {
+                    anyhow::bail!("Archive already exists: {}", archive_path.display());
+                }
+
+                if *delete_original {
+                    ActionOutput::gone().with_created(archive_path)
+                } else {
+                    ActionOutput::at(path).with_created(archive_path)
+                }
+            }
+
+            Action::Extract {
+                destination,
+                delete_archive,
+                ..
+            } => {
+                let dest = extract_target(destination.as_deref(), path, ctx)?;
+                if dest.exists() {
+                    anyhow::bail!("Destination already exists: {}", dest.display());
+                }
+
+                if *delete_archive {
+                    ActionOutput::gone().with_created(dest)
+                } else {
+                    ActionOutput::at(path).with_created(dest)
+                }
+            }
+
+            Action::Nothing => ActionOutput::at(path),
+        };
+
+        Ok(prediction)
     }
 }
 
-/// Expand ~ and environment variables in a path
-fn expand_path(path: &Path) -> PathBuf {
-    crate::expand_path(path)
+/// Remove a directory that is about to be overwritten
+///
+/// Files are replaced in place by rename/copy, but a directory would make them fail.
+fn clear_for_overwrite(target: &Path) -> Result<()> {
+    if let Ok(meta) = target.symlink_metadata()
+        && meta.is_dir()
+    {
+        std::fs::remove_dir_all(target)
+            .with_context(|| format!("Failed to remove {}", target.display()))?;
+    }
+    Ok(())
 }
 
-/// Expand pattern variables like {name}, {ext}, {date}
-fn expand_pattern(pattern: &str, path: &Path) -> Result<String> {
-    let mut result = pattern.to_string();
-
-    // {path} - full path
-    result = result.replace("{path}", &path.to_string_lossy());
+/// Expand a destination folder pattern into the file's target path inside it
+///
+/// With `{counter}` in the pattern, the counter goes up until the folder
+/// doesn't already hold a file with this name.
+fn expand_destination(destination: &Path, path: &Path, ctx: &MatchContext) -> Result<PathBuf> {
+    let filename = path.file_name().context("File has no name")?;
+    expand_unique(&destination.to_string_lossy(), path, ctx, |dest| {
+        expand_path(Path::new(&dest)).join(filename)
+    })
+}
 
-    // {dir} - parent directory
-    if let Some(parent) = path.parent() {
-        result = result.replace("{dir}", &parent.to_string_lossy());
+/// Where an archive of a file or folder is written
+fn archive_target(
+    destination: Option<&Path>,
+    format: ArchiveFormat,
+    path: &Path,
+    ctx: &MatchContext,
+) -> Result<PathBuf> {
+    let dest = match destination {
+        Some(p) => expand_path(Path::new(&expand_pattern(&p.to_string_lossy(), path, ctx)?)),
+        None => path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+    };
+
+    // Directories keep their full name, files drop the extension
+    let base_name = if ctx.data_path(path).is_dir() {
+        path.file_name()
+    } else {
+        path.file_stem()
     }
+    .context("File has no name")?;
+    Ok(dest.join(format!(
+        "{}.{}",
+        base_name.to_string_lossy(),
+        format.extension()
+    )))
+}
 
-    // {name} - filename without extension
-    if let Some(stem) = path.file_stem() {
-        result = result.replace("{name}", &stem.to_string_lossy());
+/// Folder an archive is extracted into
+fn extract_target(destination: Option<&Path>, path: &Path, ctx: &MatchContext) -> Result<PathBuf> {
+    let base_name = archive::archive_base_name(path)
+        .with_context(|| format!("Not a supported archive: {}", path.display()))?;
+    let parent = path.parent().unwrap_or(Path::new("."));
+
+    // {name} means the archive name without .zip/.tar.gz/...
+    let pattern = destination
+        .map(|d| d.to_string_lossy().to_string())
+        .unwrap_or_else(|| "{name}".to_string())
+        .replace("{name}", &base_name);
+    let dest = expand_path(Path::new(&expand_pattern(&pattern, path, ctx)?));
+    Ok(parent.join(dest))
+}
+
+/// Whether a command needs the shell (pipes, redirects, chaining)
+fn has_shell_operators(command: &str) -> bool {
+    ["&&", "||", ";", "|", ">", "<"]
+        .iter()
+        .any(|op| command.contains(op))
+}
+
+/// The command line a `run` action would execute, for plans
+fn command_line(command: &str, args: &[String], path: &Path, ctx: &MatchContext) -> Result<String> {
+    if has_shell_operators(command) && args.is_empty() {
+        return expand_command(command, path, ctx, Expansion::Shell);
+    }
+    let mut line = vec![command.to_string()];
+    for arg in args {
+        line.push(expand_command(arg, path, ctx, Expansion::Argument)?);
     }
+    Ok(line.join(" "))
+}
 
-    // {filename} - full filename with extension
-    if let Some(filename) = path.file_name() {
-        result = result.replace("{filename}", &filename.to_string_lossy());
+/// Note when a destination folder is missing and won't be created
+fn warn_missing_folder(target: &Path, create: bool, prediction: &mut Prediction) {
+    let folder = target.parent().unwrap_or(Path::new("."));
+    if !create && !folder.is_dir() {
+        prediction.warnings.push(format!(
+            "Destination folder doesn't exist: {}",
+            folder.display()
+        ));
     }
+}
 
-    // {ext} - extension
-    if let Some(ext) = path.extension() {
-        result = result.replace("{ext}", &ext.to_string_lossy());
+/// Predict how an existing file at `target` would be handled
+///
+/// Records the conflict in the prediction. With `conflict = "error"` the
+/// file stays put and the conflict's outcome says the step fails.
+fn plan_conflict(
+    strategy: ConflictStrategy,
+    pattern: Option<&str>,
+    path: &Path,
+    ctx: &MatchContext,
+    target: PathBuf,
+    prediction: &mut Prediction,
+) -> Result<Resolution> {
+    let data = ctx.data_path(path);
+    if target.symlink_metadata().is_err() || target == path || target == data {
+        return Ok(Resolution::Proceed(target));
     }
 
-    // {date} - current date
-    let now = chrono::Local::now();
-    result = result.replace("{date}", &now.format("%Y-%m-%d").to_string());
-    result = result.replace("{datetime}", &now.format("%Y-%m-%d_%H-%M-%S").to_string());
-
-    // {date:FORMAT} - custom date format
-    let date_regex = regex::Regex::new(r"\{date:([^}]+)\}")?;
-    result = date_regex
-        .replace_all(&result, |caps: &regex::Captures| {
-            let format = &caps[1];
-            now.format(format).to_string()
-        })
-        .to_string();
-
-    Ok(result)
+    let (resolution, outcome) = if strategy == ConflictStrategy::Error {
+        (Resolution::Skip, ConflictOutcome::Error)
+    } else {
+        let resolution = conflict::decide(strategy, pattern, data, &target)?;
+        let outcome = match resolution {
+            Resolution::Proceed(_) => ConflictOutcome::Rename,
+            Resolution::Overwrite(_) => ConflictOutcome::Overwrite,
+            Resolution::Skip => ConflictOutcome::Skip,
+            Resolution::Duplicate => ConflictOutcome::Duplicate,
+        };
+        (resolution, outcome)
+    };
+    prediction.conflict = Some(PlannedConflict {
+        existing: target,
+        outcome,
+    });
+    Ok(resolution)
+}
+
+/// Expand ~ and environment variables in a path
+fn expand_path(path: &Path) -> PathBuf {
+    crate::expand_path(path)
 }
 
 #[cfg(test)]
@@ -320,13 +719,143 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_expand_pattern() {
-        let path = Path::new("/tmp/test.pdf");
+    fn test_move_destination_pattern() {
+        let temp = tempfile::tempdir().unwrap();
+        let file = temp.path().join("Scan.PDF");
+        std::fs::write(&file, "pdf").unwrap();
+
+        let action = Action::Move {
+            destination: temp.path().join("sorted").join("{ext|lower}"),
+            create_destination: true,
+            overwrite: false,
+            conflict: ConflictStrategy::default(),
+            conflict_pattern: None,
+        };
+        assert_eq!(
+            action.execute(&file).unwrap(),
+            Some(temp.path().join("sorted").join("pdf").join("Scan.PDF"))
+        );
+    }
+
+    #[test]
+    fn test_move_conflict_strategies() {
+        let temp = tempfile::tempdir().unwrap();
+        let dest = temp.path().join("dest");
+        std::fs::create_dir(&dest).unwrap();
+        std::fs::write(dest.join("a.txt"), "same").unwrap();
+
+        let move_with = |conflict| Action::Move {
+            destination: dest.clone(),
+            create_destination: true,
+            overwrite: false,
+            conflict,
+            conflict_pattern: None,
+        };
+
+        let file = temp.path().join("a.txt");
+        std::fs::write(&file, "other").unwrap();
+        assert_eq!(
+            move_with(ConflictStrategy::Skip).execute(&file).unwrap(),
+            Some(file.clone())
+        );
+        assert_eq!(
+            move_with(ConflictStrategy::Rename).execute(&file).unwrap(),
+            Some(dest.join("a (2).txt"))
+        );
+
+        std::fs::write(&file, "same").unwrap();
+        assert_eq!(
+            move_with(ConflictStrategy::Deduplicate)
+                .execute(&file)
+                .unwrap(),
+            None
+        );
+        assert!(!file.exists());
+        assert_eq!(std::fs::read_to_string(dest.join("a.txt")).unwrap(), "same");
+    }
+
+    #[test]
+    fn test_rename_does_not_overwrite_by_default() {
+        let temp = tempfile::tempdir().unwrap();
+        let file = temp.path().join("a.txt");
+        std::fs::write(&file, "a").unwrap();
+        std::fs::write(temp.path().join("a.md"), "existing").unwrap();
+
+        let action = Action::Rename {
+            pattern: "{name}.md".to_string(),
+            conflict: ConflictStrategy::default(),
+            conflict_pattern: None,
+        };
+        assert!(action.execute(&file).is_err());
+        assert_eq!(
+            std::fs::read_to_string(temp.path().join("a.md")).unwrap(),
+            "existing"
+        );
+    }
 
-        assert_eq!(expand_pattern("{name}", path).unwrap(), "test");
-        assert_eq!(expand_pattern("{ext}", path).unwrap(), "pdf");
-        assert_eq!(expand_pattern("{filename}", path).unwrap(), "test.pdf");
-        assert_eq!(expand_pattern("{name}.{ext}", path).unwrap(), "test.pdf");
+    #[test]
+    fn test_archive_keeps_original_on_failure() {
+        let temp = tempfile::tempdir().unwrap();
+        let file = temp.path().join("report.txt");
+        std::fs::write(&file, "data").unwrap();
+
+        // An existing archive blocks the action before anything is deleted
+        std::fs::write(temp.path().join("report.zip"), "not a zip").unwrap();
+
+        let action = Action::Archive {
+            destination: None,
+            delete_original: true,
+            format: ArchiveFormat::Zip,
+            level: None,
+        };
+        assert!(action.execute(&file).is_err());
+        assert!(file.exists());
+    }
+
+    #[test]
+    fn test_archive_deletes_original_after_success() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("photos");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join("a.jpg"), "jpg").unwrap();
+
+        let action = Action::Archive {
+            destination: Some(temp.path().join("archives")),
+            delete_original: true,
+            format: ArchiveFormat::TarGz,
+            level: Some(9),
+        };
+        let archive_path = temp.path().join("archives").join("photos.tar.gz");
+        assert_eq!(
+            action.perform_with(&dir, &MatchContext::default()).unwrap(),
+            ActionOutput {
+                path: None,
+                created: Some(archive_path.clone()),
+            }
+        );
+        assert!(!dir.exists());
+        assert!(archive_path.exists());
+    }
+
+    #[test]
+    fn test_extract_next_to_archive() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("bundle");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join("readme.txt"), "hi").unwrap();
+
+        let archive_path = temp.path().join("bundle.tar.zst");
+        archive::create_archive(&dir, &archive_path, ArchiveFormat::TarZst, None).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let action = Action::Extract {
+            destination: None,
+            max_size: None,
+            delete_archive: true,
+        };
+        assert_eq!(action.execute(&archive_path).unwrap(), None);
+        assert!(!archive_path.exists());
+        assert!(temp.path().join("bundle/bundle/readme.txt").exists());
     }
 
     #[test]
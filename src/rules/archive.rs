@@ -0,0 +1,872 @@
+//! Archive creation and extraction for the archive/extract actions
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Archive format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ArchiveFormat {
+    /// Zip (deflate)
+    #[default]
+    #[serde(rename = "zip")]
+    Zip,
+
+    /// Tar compressed with gzip
+    #[serde(rename = "tar.gz", alias = "tgz")]
+    TarGz,
+
+    /// Tar compressed with zstd
+    #[serde(rename = "tar.zst", alias = "tzst")]
+    TarZst,
+}
+
+impl ArchiveFormat {
+    /// File extension for this format (without leading dot)
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarZst => "tar.zst",
+        }
+    }
+}
+
+impl std::fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// Kind of entry stored in an archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum EntryKind {
+    File { size: u64 },
+    Dir,
+    Symlink { target: PathBuf },
+}
+
+/// A file system entry to be archived
+#[derive(Debug)]
+struct Entry {
+    /// Name inside the archive ('/' separated)
+    name: String,
+    /// Path on disk
+    path: PathBuf,
+    kind: EntryKind,
+}
+
+/// Create an archive of `source` (a file or directory) at `archive_path`
+///
+/// The archive is written to a temporary file next to `archive_path`, read back
+/// to verify every entry, and only then moved into place. On failure nothing is
+/// left behind.
+pub fn create_archive(
+    source: &Path,
+    archive_path: &Path,
+    format: ArchiveFormat,
+    level: Option<i32>,
+) -> Result<()> {
+    let entries = collect_entries(source)?;
+
+    let file_name = archive_path
+        .file_name()
+        .context("Archive path has no file name")?;
+    let temp_path =
+        archive_path.with_file_name(format!(".{}.partial", file_name.to_string_lossy()));
+
+    let result = write_archive(&entries, &temp_path, format, level)
+        .and_then(|()| verify_archive(&temp_path, format, &entries))
+        .and_then(|()| {
+            std::fs::rename(&temp_path, archive_path).with_context(|| {
+                format!(
+                    "Failed to move archive into place: {}",
+                    archive_path.display()
+                )
+            })
+        });
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+/// Walk `source` and collect everything that goes into the archive
+fn collect_entries(source: &Path) -> Result<Vec<Entry>> {
+    let root_name = source
+        .file_name()
+        .context("Source has no name")?
+        .to_string_lossy()
+        .to_string();
+
+    let mut entries = Vec::new();
+    collect_into(source, root_name, &mut entries)?;
+    Ok(entries)
+}
+
+fn collect_into(path: &Path, name: String, entries: &mut Vec<Entry>) -> Result<()> {
+    let metadata = std::fs::symlink_metadata(path)
+        .with_context(|| format!("Failed to read metadata: {}", path.display()))?;
+    let file_type = metadata.file_type();
+
+    if file_type.is_symlink() {
+        let target = std::fs::read_link(path)?;
+        entries.push(Entry {
+            name,
+            path: path.to_path_buf(),
+            kind: EntryKind::Symlink { target },
+        });
+    } else if file_type.is_dir() {
+        entries.push(Entry {
+            name: name.clone(),
+            path: path.to_path_buf(),
+            kind: EntryKind::Dir,
+        });
+
+        let mut children: Vec<_> = std::fs::read_dir(path)
+            .with_context(|| format!("Failed to read directory: {}", path.display()))?
+            .collect::<io::Result<_>>()?;
+        children.sort_by_key(|c| c.file_name());
+
+        for child in children {
+            let child_name = format!("{}/{}", name, child.file_name().to_string_lossy());
+            collect_into(&child.path(), child_name, entries)?;
+        }
+    } else if file_type.is_file() {
+        entries.push(Entry {
+            name,
+            path: path.to_path_buf(),
+            kind: EntryKind::File {
+                size: metadata.len(),
+            },
+        });
+    } else {
+        anyhow::bail!("Cannot archive special file: {}", path.display());
+    }
+
+    Ok(())
+}
+
+fn write_archive(
+    entries: &[Entry],
+    archive_path: &Path,
+    format: ArchiveFormat,
+    level: Option<i32>,
+) -> Result<()> {
+    let file = File::create(archive_path)
+        .with_context(|| format!("Failed to create archive: {}", archive_path.display()))?;
+    let writer = BufWriter::new(file);
+
+    let writer = match format {
+        ArchiveFormat::Zip => write_zip(entries, writer, level)?,
+        ArchiveFormat::TarGz => {
+            let compression = level
+                .map(|l| flate2::Compression::new(l.clamp(0, 9) as u32))
+                .unwrap_or_default();
+            let encoder = flate2::write::GzEncoder::new(writer, compression);
+            write_tar(entries, encoder)?.finish()?
+        }
+        ArchiveFormat::TarZst => {
+            let mut encoder = zstd::Encoder::new(writer, level.unwrap_or(0))?;
+            encoder.include_checksum(true)?;
+            write_tar(entries, encoder)?.finish()?
+        }
+    };
+
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn write_zip<W: Write + io::Seek>(entries: &[Entry], writer: W, level: Option<i32>) -> Result<W> {
+    use zip::write::SimpleFileOptions;
+
+    let mut zip = zip::ZipWriter::new(writer);
+    let options = SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .compression_level(level.map(i64::from));
+
+    for entry in entries {
+        match &entry.kind {
+            EntryKind::File { size } => {
+                let mut options = options.large_file(*size >= u32::MAX as u64);
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    let mode = std::fs::metadata(&entry.path)?.permissions().mode();
+                    options = options.unix_permissions(mode);
+                }
+                zip.start_file(entry.name.as_str(), options)?;
+                let mut input = File::open(&entry.path)
+                    .with_context(|| format!("Failed to open {}", entry.path.display()))?;
+                io::copy(&mut input, &mut zip)?;
+            }
+            EntryKind::Dir => {
+                zip.add_directory(entry.name.as_str(), options)?;
+            }
+            EntryKind::Symlink { target } => {
+                zip.add_symlink(
+                    entry.name.as_str(),
+                    target.to_string_lossy().as_ref(),
+                    options,
+                )?;
+            }
+        }
+    }
+
+    Ok(zip.finish()?)
+}
+
+fn write_tar<W: Write>(entries: &[Entry], writer: W) -> Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    builder.follow_symlinks(false);
+
+    for entry in entries {
+        match &entry.kind {
+            EntryKind::File { .. } => {
+                let mut input = File::open(&entry.path)
+                    .with_context(|| format!("Failed to open {}", entry.path.display()))?;
+                builder.append_file(&entry.name, &mut input)?;
+            }
+            EntryKind::Dir => {
+                builder.append_dir(&entry.name, &entry.path)?;
+            }
+            EntryKind::Symlink { target } => {
+                let metadata = std::fs::symlink_metadata(&entry.path)?;
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata(&metadata);
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_size(0);
+                builder.append_link(&mut header, &entry.name, target)?;
+            }
+        }
+    }
+
+    Ok(builder.into_inner()?)
+}
+
+/// Read the archive back and check it holds every entry with the right size
+fn verify_archive(archive_path: &Path, format: ArchiveFormat, entries: &[Entry]) -> Result<()> {
+    let mut expected: HashMap<&str, &EntryKind> =
+        entries.iter().map(|e| (e.name.as_str(), &e.kind)).collect();
+
+    let mut check = |name: &str, kind: EntryKind| -> Result<()> {
+        let name = name.trim_end_matches('/');
+        match expected.remove(name) {
+            Some(want) if *want == kind => Ok(()),
+            Some(want) => anyhow::bail!(
+                "Archive verification failed for '{}': expected {:?}, found {:?}",
+                name,
+                want,
+                kind
+            ),
+            None => anyhow::bail!("Archive verification failed: unexpected entry '{}'", name),
+        }
+    };
+
+    let file = File::open(archive_path)?;
+
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = zip::ZipArchive::new(file)?;
+            for i in 0..zip.len() {
+                let mut entry = zip.by_index(i)?;
+                let name = entry.name()?.to_string();
+                let kind = if entry.is_dir() {
+                    EntryKind::Dir
+                } else if entry.is_symlink() {
+                    let mut target = String::new();
+                    entry.read_to_string(&mut target)?;
+                    EntryKind::Symlink {
+                        target: PathBuf::from(target),
+                    }
+                } else {
+                    // Reading the data checks the CRC
+                    let size = io::copy(&mut entry, &mut io::sink())?;
+                    EntryKind::File { size }
+                };
+                check(&name, kind)?;
+            }
+        }
+        ArchiveFormat::TarGz => {
+            verify_tar(flate2::read::GzDecoder::new(file), &mut check)?;
+        }
+        ArchiveFormat::TarZst => {
+            verify_tar(zstd::Decoder::new(file)?, &mut check)?;
+        }
+    }
+
+    if let Some(missing) = expected.keys().next() {
+        anyhow::bail!("Archive verification failed: missing entry '{}'", missing);
+    }
+
+    debug!("Verified archive {}", archive_path.display());
+    Ok(())
+}
+
+fn verify_tar<R: Read>(
+    reader: R,
+    check: &mut impl FnMut(&str, EntryKind) -> Result<()>,
+) -> Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        let kind = match entry.header().entry_type() {
+            tar::EntryType::Directory => EntryKind::Dir,
+            tar::EntryType::Symlink => EntryKind::Symlink {
+                target: entry
+                    .link_name()?
+                    .map(|t| t.into_owned())
+                    .unwrap_or_default(),
+            },
+            _ => EntryKind::File {
+                size: io::copy(&mut entry, &mut io::sink())?,
+            },
+        };
+        check(&name, kind)?;
+    }
+    Ok(())
+}
+
+/// Default limit for the total size of extracted files (10 GiB)
+pub const DEFAULT_MAX_EXTRACT_SIZE: u64 = 10 * 1024 * 1024 * 1024;
+
+/// Compression wrapped around a tar stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TarCompression {
+    None,
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+/// Archive types that can be extracted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExtractKind {
+    Zip,
+    Tar(TarCompression),
+}
+
+/// Recognized archive suffixes, longest first
+const EXTRACT_SUFFIXES: &[(&str, ExtractKind)] = &[
+    (".tar.gz", ExtractKind::Tar(TarCompression::Gzip)),
+    (".tar.xz", ExtractKind::Tar(TarCompression::Xz)),
+    (".tar.zst", ExtractKind::Tar(TarCompression::Zstd)),
+    (".tgz", ExtractKind::Tar(TarCompression::Gzip)),
+    (".txz", ExtractKind::Tar(TarCompression::Xz)),
+    (".tzst", ExtractKind::Tar(TarCompression::Zstd)),
+    (".tar", ExtractKind::Tar(TarCompression::None)),
+    (".zip", ExtractKind::Zip),
+];
+
+/// Split an archive file name into its base name and archive type
+fn detect_extract_kind(path: &Path) -> Option<(String, ExtractKind)> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let lower = name.to_lowercase();
+    EXTRACT_SUFFIXES.iter().find_map(|(suffix, kind)| {
+        lower
+            .strip_suffix(suffix)
+            .filter(|base| !base.is_empty())
+            .map(|base| (name[..base.len()].to_string(), *kind))
+    })
+}
+
+/// Name of an archive without its archive extension (`photos.tar.gz` → `photos`)
+///
+/// Returns `None` if the file isn't a supported archive.
+pub fn archive_base_name(path: &Path) -> Option<String> {
+    detect_extract_kind(path).map(|(base, _)| base)
+}
+
+/// Extract a zip or tar (optionally gzip/xz/zstd compressed) archive into `destination`
+///
+/// Entries are unpacked into a temporary folder next to `destination`, which is
+/// only renamed into place once everything was extracted. Entries that would
+/// escape the destination (absolute paths, `..`, links pointing outside) abort
+/// the extraction, as does exceeding `max_size` bytes of extracted data.
+pub fn extract_archive(archive_path: &Path, destination: &Path, max_size: u64) -> Result<()> {
+    let (_, kind) = detect_extract_kind(archive_path)
+        .with_context(|| format!("Unsupported archive type: {}", archive_path.display()))?;
+
+    if destination.exists() {
+        anyhow::bail!("Destination already exists: {}", destination.display());
+    }
+
+    let parent = destination
+        .parent()
+        .context("Destination has no parent directory")?;
+    std::fs::create_dir_all(parent)
+        .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+
+    let dest_name = destination.file_name().context("Destination has no name")?;
+    let staging = parent.join(format!(".{}.partial", dest_name.to_string_lossy()));
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    std::fs::create_dir(&staging)?;
+
+    let file = File::open(archive_path)
+        .with_context(|| format!("Failed to open archive: {}", archive_path.display()))?;
+
+    let result = match kind {
+        ExtractKind::Zip => extract_zip(file, &staging, max_size),
+        ExtractKind::Tar(TarCompression::None) => extract_tar(file, &staging, max_size),
+        ExtractKind::Tar(TarCompression::Gzip) => {
+            extract_tar(flate2::read::GzDecoder::new(file), &staging, max_size)
+        }
+        ExtractKind::Tar(TarCompression::Xz) => {
+            extract_tar(xz2::read::XzDecoder::new(file), &staging, max_size)
+        }
+        ExtractKind::Tar(TarCompression::Zstd) => zstd::Decoder::new(file)
+            .map_err(anyhow::Error::from)
+            .and_then(|decoder| extract_tar(decoder, &staging, max_size)),
+    }
+    .and_then(|()| {
+        std::fs::rename(&staging, destination).with_context(|| {
+            format!(
+                "Failed to move extracted files into place: {}",
+                destination.display()
+            )
+        })
+    });
+
+    if result.is_err() {
+        let _ = std::fs::remove_dir_all(&staging);
+    }
+
+    result
+}
+
+/// Check that an archive entry path stays inside the destination
+fn safe_entry_path(name: &Path) -> Result<PathBuf> {
+    let mut safe = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => safe.push(part),
+            Component::CurDir => {}
+            _ => anyhow::bail!(
+                "Refusing to extract entry outside destination: {}",
+                name.display()
+            ),
+        }
+    }
+    if safe.as_os_str().is_empty() {
+        anyhow::bail!("Archive entry has an empty name");
+    }
+    Ok(safe)
+}
+
+/// Check that a link stored in an archive points inside the destination
+///
+/// `entry` is the link's own (already sanitized) path, relative to the
+/// destination root.
+fn check_link_target(entry: &Path, target: &Path) -> Result<()> {
+    let mut depth = entry.components().count().saturating_sub(1) as isize;
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => depth -= 1,
+            _ => depth = -1,
+        }
+        if depth < 0 {
+            anyhow::bail!(
+                "Refusing to extract link pointing outside destination: {} -> {}",
+                entry.display(),
+                target.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Check that no folder on an entry's path, nor the entry itself, is a link
+/// written by an earlier entry
+///
+/// Writing through a link is never needed for a valid archive, and the entry
+/// could end up outside the destination.
+fn check_no_links_on_path(staging: &Path, relative: &Path) -> Result<()> {
+    let mut path = staging.to_path_buf();
+    for component in relative.components() {
+        path.push(component);
+        if path
+            .symlink_metadata()
+            .is_ok_and(|m| m.file_type().is_symlink())
+        {
+            anyhow::bail!(
+                "Refusing to extract entry through a link: {}",
+                relative.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Check that no extracted link's target goes through another link
+///
+/// Targets are checked on their own as links are written, but `..` after a
+/// link leaves from the link's target, not from where the link is. A chain of
+/// links that each stay inside can still lead out (`d/l -> ../..` plus
+/// `l2 -> d/l/..`), so once everything is extracted, a link may only point
+/// at another link, never through one.
+fn check_link_chains(staging: &Path, links: &[(PathBuf, PathBuf)]) -> Result<()> {
+    for (link, target) in links {
+        let mut resolved = link.parent().map(Path::to_path_buf).unwrap_or_default();
+        let mut components = target.components().peekable();
+        while let Some(component) = components.next() {
+            match component {
+                Component::Normal(part) => resolved.push(part),
+                Component::ParentDir => {
+                    resolved.pop();
+                    continue;
+                }
+                _ => continue,
+            }
+            let is_link = staging
+                .join(&resolved)
+                .symlink_metadata()
+                .is_ok_and(|m| m.file_type().is_symlink());
+            if is_link && components.peek().is_some() {
+                anyhow::bail!(
+                    "Refusing to extract link pointing through another link: {} -> {}",
+                    link.display(),
+                    target.display()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn extract_zip(file: File, staging: &Path, max_size: u64) -> Result<()> {
+    let mut zip = zip::ZipArchive::new(file)?;
+    let mut total: u64 = 0;
+    let mut links = Vec::new();
+
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        let name = entry.name()?.to_string();
+        let relative = safe_entry_path(Path::new(&name))?;
+        let out_path = staging.join(&relative);
+        check_no_links_on_path(staging, &relative)?;
+
+        if entry.is_dir() {
+            std::fs::create_dir_all(&out_path)?;
+            continue;
+        }
+
+        if let Some(parent) = out_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        if entry.is_symlink() {
+            let mut target = String::new();
+            entry.read_to_string(&mut target)?;
+            check_link_target(&relative, Path::new(&target))?;
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(&target, &out_path)?;
+            #[cfg(not(unix))]
+            warn!("Skipping symlink {} (unsupported on this platform)", name);
+            links.push((relative, PathBuf::from(target)));
+            continue;
+        }
+
+        let remaining = max_size.saturating_sub(total);
+        let mut out = File::create(&out_path)
+            .with_context(|| format!("Failed to create {}", out_path.display()))?;
+        // Don't trust the size in the header: read at most one byte past the limit
+        let written = io::copy(&mut (&mut entry).take(remaining + 1), &mut out)?;
+        total += written;
+        if total > max_size {
+            anyhow::bail!(
+                "Archive expands beyond the size limit of {} bytes",
+                max_size
+            );
+        }
+
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&out_path, std::fs::Permissions::from_mode(mode & 0o777))?;
+        }
+    }
+
+    check_link_chains(staging, &links)
+}
+
+fn extract_tar<R: Read>(reader: R, staging: &Path, max_size: u64) -> Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_permissions(false);
+    archive.set_unpack_xattrs(false);
+
+    let mut total: u64 = 0;
+    let mut links = Vec::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let relative = safe_entry_path(&entry.path()?)?;
+        check_no_links_on_path(staging, &relative)?;
+
+        match entry.header().entry_type() {
+            tar::EntryType::Symlink | tar::EntryType::Link => {
+                let target = entry
+                    .link_name()?
+                    .context("Link entry without a target")?
+                    .into_owned();
+                if entry.header().entry_type() == tar::EntryType::Link {
+                    // Hard link targets are relative to the archive root
+                    check_no_links_on_path(staging, &safe_entry_path(&target)?)?;
+                } else {
+                    check_link_target(&relative, &target)?;
+                    links.push((relative.clone(), target));
+                }
+            }
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                total += entry.size();
+                if total > max_size {
+                    anyhow::bail!(
+                        "Archive expands beyond the size limit of {} bytes",
+                        max_size
+                    );
+                }
+            }
+            tar::EntryType::Directory => {}
+            other => {
+                warn!(
+                    "Skipping unsupported tar entry {:?}: {}",
+                    other,
+                    relative.display()
+                );
+                continue;
+            }
+        }
+
+        // unpack_in also refuses to write through links that leave the folder
+        if !entry.unpack_in(staging)? {
+            anyhow::bail!(
+                "Refusing to extract entry outside destination: {}",
+                relative.display()
+            );
+        }
+    }
+
+    check_link_chains(staging, &links)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_dir(root: &Path) -> PathBuf {
+        let dir = root.join("project");
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("README.md"), "hello").unwrap();
+        std::fs::write(dir.join("src").join("main.rs"), "fn main() {}").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_archive_directory_all_formats() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = sample_dir(temp.path());
+
+        for format in [
+            ArchiveFormat::Zip,
+            ArchiveFormat::TarGz,
+            ArchiveFormat::TarZst,
+        ] {
+            let archive = temp.path().join(format!("project.{}", format.extension()));
+            create_archive(&dir, &archive, format, Some(3)).unwrap();
+            assert!(archive.exists(), "{} archive missing", format);
+
+            // Nothing temporary left behind
+            let leftovers: Vec<_> = std::fs::read_dir(temp.path())
+                .unwrap()
+                .flatten()
+                .filter(|e| e.file_name().to_string_lossy().ends_with(".partial"))
+                .collect();
+            assert!(leftovers.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_archive_single_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let file = temp.path().join("notes.txt");
+        std::fs::write(&file, "some notes").unwrap();
+
+        let archive = temp.path().join("notes.zip");
+        create_archive(&file, &archive, ArchiveFormat::Zip, None).unwrap();
+
+        let mut zip = zip::ZipArchive::new(File::open(&archive).unwrap()).unwrap();
+        assert_eq!(zip.len(), 1);
+        let mut content = String::new();
+        zip.by_name("notes.txt")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "some notes");
+    }
+
+    #[test]
+    fn test_parse_format() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            format: ArchiveFormat,
+        }
+
+        let parse = |s: &str| {
+            toml::from_str::<Wrapper>(&format!("format = \"{}\"", s))
+                .unwrap()
+                .format
+        };
+        assert_eq!(parse("zip"), ArchiveFormat::Zip);
+        assert_eq!(parse("tar.gz"), ArchiveFormat::TarGz);
+        assert_eq!(parse("tgz"), ArchiveFormat::TarGz);
+        assert_eq!(parse("tar.zst"), ArchiveFormat::TarZst);
+    }
+
+    #[test]
+    fn test_extract_roundtrip() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = sample_dir(temp.path());
+
+        for format in [
+            ArchiveFormat::Zip,
+            ArchiveFormat::TarGz,
+            ArchiveFormat::TarZst,
+        ] {
+            let archive = temp.path().join(format!("project.{}", format.extension()));
+            create_archive(&dir, &archive, format, None).unwrap();
+
+            let out = temp.path().join(format!("out-{}", format.extension()));
+            extract_archive(&archive, &out, DEFAULT_MAX_EXTRACT_SIZE).unwrap();
+            let main = std::fs::read_to_string(out.join("project/src/main.rs")).unwrap();
+            assert_eq!(main, "fn main() {}");
+        }
+    }
+
+    #[test]
+    fn test_extract_rejects_path_traversal() {
+        let temp = tempfile::tempdir().unwrap();
+        let archive = temp.path().join("evil.zip");
+
+        let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+        zip.start_file("../escaped.txt", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"gotcha").unwrap();
+        zip.finish().unwrap();
+
+        let out = temp.path().join("out").join("evil");
+        assert!(extract_archive(&archive, &out, DEFAULT_MAX_EXTRACT_SIZE).is_err());
+        assert!(!temp.path().join("out").join("escaped.txt").exists());
+        assert!(!out.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_rejects_writing_through_chained_links() {
+        let temp = tempfile::tempdir().unwrap();
+        let archive = temp.path().join("chain.zip");
+        let options = zip::write::SimpleFileOptions::default();
+
+        // Each link stays inside on its own, but d1/l2 resolves to the parent
+        // of the extraction folder
+        let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+        zip.add_directory("d1/d2/", options).unwrap();
+        zip.add_symlink("d1/d2/l", "../..", options).unwrap();
+        zip.add_symlink("d1/l2", "d2/l/..", options).unwrap();
+        zip.start_file("d1/l2/x", options).unwrap();
+        zip.write_all(b"gotcha").unwrap();
+        zip.finish().unwrap();
+
+        let out = temp.path().join("out").join("chain");
+        let err = extract_archive(&archive, &out, DEFAULT_MAX_EXTRACT_SIZE).unwrap_err();
+        assert!(err.to_string().contains("through a link"), "{:#}", err);
+        assert!(!temp.path().join("out").join("x").exists());
+        assert!(!temp.path().join("x").exists());
+        assert!(!out.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_extract_rejects_chained_links() {
+        let temp = tempfile::tempdir().unwrap();
+
+        // Nothing is written through them, but left in place d1/l2 would
+        // lead to the parent of the extraction folder
+        let zip_path = temp.path().join("links.zip");
+        let options = zip::write::SimpleFileOptions::default();
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        zip.add_directory("d1/d2/", options).unwrap();
+        zip.add_symlink("d1/d2/l", "../..", options).unwrap();
+        zip.add_symlink("d1/l2", "d2/l/..", options).unwrap();
+        zip.finish().unwrap();
+
+        let tar_path = temp.path().join("links.tar");
+        let mut tar = tar::Builder::new(File::create(&tar_path).unwrap());
+        for (name, target) in [("d1/d2/l", "../.."), ("d1/l2", "d2/l/..")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            tar.append_link(&mut header, name, target).unwrap();
+        }
+        tar.finish().unwrap();
+
+        for archive in [zip_path, tar_path] {
+            let out = temp.path().join("out").join("links");
+            let err = extract_archive(&archive, &out, DEFAULT_MAX_EXTRACT_SIZE).unwrap_err();
+            assert!(
+                err.to_string().contains("through another link"),
+                "{:#}",
+                err
+            );
+            assert!(!out.exists());
+        }
+
+        // Links to links are fine
+        let ok = temp.path().join("ok.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&ok).unwrap());
+        zip.add_directory("docs/", options).unwrap();
+        zip.add_symlink("latest", "docs", options).unwrap();
+        zip.add_symlink("current", "latest", options).unwrap();
+        zip.finish().unwrap();
+        let out = temp.path().join("ok");
+        extract_archive(&ok, &out, DEFAULT_MAX_EXTRACT_SIZE).unwrap();
+        assert!(out.join("current").is_dir());
+    }
+
+    #[test]
+    fn test_extract_enforces_size_limit() {
+        let temp = tempfile::tempdir().unwrap();
+        let file = temp.path().join("big.bin");
+        std::fs::write(&file, vec![0u8; 64 * 1024]).unwrap();
+        let archive = temp.path().join("big.tar.gz");
+        create_archive(&file, &archive, ArchiveFormat::TarGz, None).unwrap();
+
+        let out = temp.path().join("big");
+        let err = extract_archive(&archive, &out, 1024).unwrap_err();
+        assert!(err.to_string().contains("size limit"));
+        assert!(!out.exists());
+    }
+
+    #[test]
+    fn test_archive_base_name() {
+        assert_eq!(
+            archive_base_name(Path::new("/tmp/photos.tar.gz")).as_deref(),
+            Some("photos")
+        );
+        assert_eq!(
+            archive_base_name(Path::new("/tmp/Bundle.v2.ZIP")).as_deref(),
+            Some("Bundle.v2")
+        );
+        assert_eq!(archive_base_name(Path::new("/tmp/notes.txt")), None);
+    }
+}
@@ -3,7 +3,14 @@
 use anyhow::Result;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use super::MatchContext;
+use super::content;
+use super::mime::{self, FileKind};
+use super::units::{Age, ByteSize};
 
 /// Conditions for matching files
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -24,21 +31,49 @@ pub struct Condition {
     #[serde(default)]
     pub name_regex: Option<String>,
 
-    /// File size greater than (in bytes)
-    #[serde(default)]
-    pub size_greater_than: Option<u64>,
+    /// Match the path relative to the watch root with a glob (`**` spans folders)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_matches: Option<String>,
 
-    /// File size less than (in bytes)
-    #[serde(default)]
-    pub size_less_than: Option<u64>,
+    /// Match the path relative to the watch root with a regex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_regex: Option<String>,
 
-    /// File age greater than (in days)
+    /// File size greater than (bytes, or with a unit like "25MB")
     #[serde(default)]
-    pub age_days_greater_than: Option<u64>,
+    pub size_greater_than: Option<ByteSize>,
 
-    /// File age less than (in days)
+    /// File size less than (bytes, or with a unit like "1.5GiB")
     #[serde(default)]
-    pub age_days_less_than: Option<u64>,
+    pub size_less_than: Option<ByteSize>,
+
+    /// File age greater than (days, or with a unit like "36h")
+    #[serde(default, alias = "age_greater_than")]
+    pub age_days_greater_than: Option<Age>,
+
+    /// File age less than (days, or with a unit like "90m")
+    #[serde(default, alias = "age_less_than")]
+    pub age_days_less_than: Option<Age>,
+
+    /// Added to its folder longer ago than this (days, or with a unit like "36h")
+    #[serde(
+        default,
+        alias = "added_days_greater_than",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub added_greater_than: Option<Age>,
+
+    /// Added to its folder more recently than this (days, or with a unit)
+    #[serde(
+        default,
+        alias = "added_days_less_than",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub added_less_than: Option<Age>,
+
+    /// Timestamp the age conditions look at (default: modified)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_timestamp: Option<AgeTimestamp>,
 
     /// File is a directory
     #[serde(default)]
@@ -47,11 +82,106 @@ pub struct Condition {
     /// File is hidden (starts with .)
     #[serde(default)]
     pub is_hidden: Option<bool>,
+
+    /// MIME type sniffed from the content (e.g., "application/pdf" or "image/*")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime: Option<String>,
+
+    /// Sniffed MIME type starts with this (e.g., "video/")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type_prefix: Option<String>,
+
+    /// Built-in file kind (image, video, audio, document, archive, code, font, disk-image)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<FileKind>,
+
+    /// File text contains this string (case-sensitive)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_contains: Option<String>,
+
+    /// File text matches this regex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_regex: Option<String>,
+
+    /// Bytes of text examined by content conditions (default 1 MiB)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_read_limit: Option<ByteSize>,
+
+    /// Text encoding for content conditions (e.g., "utf-16le"), detected if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_encoding: Option<String>,
+
+    /// Nested conditions where at least one must match (OR)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub any: Vec<Condition>,
+
+    /// Nested conditions that must all match (AND)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub all: Vec<Condition>,
+
+    /// Nested condition that must not match (NOT)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not: Option<Box<Condition>>,
+}
+
+/// File timestamp used by the age conditions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgeTimestamp {
+    /// Last modification (default)
+    #[default]
+    Modified,
+    /// Creation (modification time if the filesystem doesn't record it)
+    Created,
+    /// Last access
+    Accessed,
+    /// When the file was added to its folder, as tracked by the watcher
+    Added,
+}
+
+impl AgeTimestamp {
+    /// Read this timestamp from a file's metadata
+    pub fn of(self, metadata: &std::fs::Metadata) -> std::io::Result<SystemTime> {
+        match self {
+            Self::Modified => metadata.modified(),
+            Self::Created => metadata.created().or_else(|_| metadata.modified()),
+            Self::Accessed => metadata.accessed(),
+            Self::Added => status_change_time(metadata),
+        }
+    }
+}
+
+/// Estimate when a file was added to its folder, for files the watcher hasn't tracked
+///
+/// Moving, renaming or copying a file into a folder updates its status change
+/// time (ctime) on Unix, unlike its modification time.
+#[cfg(unix)]
+fn status_change_time(metadata: &std::fs::Metadata) -> std::io::Result<SystemTime> {
+    use std::os::unix::fs::MetadataExt;
+    let since_epoch = Duration::new(
+        u64::try_from(metadata.ctime()).unwrap_or(0),
+        u32::try_from(metadata.ctime_nsec()).unwrap_or(0),
+    );
+    Ok(SystemTime::UNIX_EPOCH + since_epoch)
+}
+
+#[cfg(not(unix))]
+fn status_change_time(metadata: &std::fs::Metadata) -> std::io::Result<SystemTime> {
+    metadata.created().or_else(|_| metadata.modified())
 }
 
 impl Condition {
     /// Check if a file matches this condition
     pub fn matches(&self, path: &Path) -> Result<bool> {
+        self.matches_with(path, &mut MatchContext::default())
+    }
+
+    /// Check if a file matches, recording regex captures into `ctx` on a match
+    ///
+    /// Captures from `not` groups and non-matching `any` branches are discarded.
+    pub fn matches_with(&self, path: &Path, ctx: &mut MatchContext) -> Result<bool> {
+        let mut local = ctx.scope();
+        let data = local.data_path(path).to_path_buf();
         // Check extension
         if let Some(ref ext) = self.extension
             && !check_extension(path, ext)
@@ -75,54 +205,79 @@ impl Condition {
         }
 
         // Check name regex
-        if let Some(ref pattern) = self.name_regex
-            && !check_regex(path, pattern)?
-        {
-            return Ok(false);
+        if let Some(ref pattern) = self.name_regex {
+            match regex_captures(path, pattern)? {
+                Some(captures) => local.captures.extend(captures),
+                None => return Ok(false),
+            }
+        }
+
+        // Check path relative to the watch root
+        if self.path_matches.is_some() || self.path_regex.is_some() {
+            let relative = relative_path_string(path, ctx);
+
+            if let Some(ref pattern) = self.path_matches
+                && !check_path_glob(&relative, pattern)?
+            {
+                return Ok(false);
+            }
+
+            if let Some(ref pattern) = self.path_regex {
+                let regex = Regex::new(pattern)?;
+                let Some(caps) = regex.captures(&relative) else {
+                    return Ok(false);
+                };
+                insert_named_captures(&regex, &caps, &mut local);
+            }
         }
 
         // Check file size
         if (self.size_greater_than.is_some() || self.size_less_than.is_some())
-            && let Ok(metadata) = path.metadata()
+            && let Ok(metadata) = data.metadata()
         {
             let size = metadata.len();
 
             if let Some(min) = self.size_greater_than
-                && size <= min
+                && size <= min.bytes()
             {
                 return Ok(false);
             }
 
             if let Some(max) = self.size_less_than
-                && size >= max
+                && size >= max.bytes()
             {
                 return Ok(false);
             }
         }
 
         // Check file age
-        if (self.age_days_greater_than.is_some() || self.age_days_less_than.is_some())
-            && let Ok(metadata) = path.metadata()
-            && let Ok(modified) = metadata.modified()
-        {
-            let age = modified.elapsed().map(|d| d.as_secs() / 86400).unwrap_or(0);
-
-            if let Some(min_days) = self.age_days_greater_than
-                && age <= min_days
+        if self.age_days_greater_than.is_some() || self.age_days_less_than.is_some() {
+            let timestamp = match self.age_timestamp.unwrap_or_default() {
+                AgeTimestamp::Added => added_time(path, ctx),
+                which => data.metadata().and_then(|m| which.of(&m)).ok(),
+            };
+            if let Some(timestamp) = timestamp
+                && !check_age(
+                    timestamp,
+                    self.age_days_greater_than,
+                    self.age_days_less_than,
+                )
             {
                 return Ok(false);
             }
+        }
 
-            if let Some(max_days) = self.age_days_less_than
-                && age >= max_days
-            {
-                return Ok(false);
-            }
+        // Check time since the file was added to its folder
+        if (self.added_greater_than.is_some() || self.added_less_than.is_some())
+            && let Some(added) = added_time(path, ctx)
+            && !check_age(added, self.added_greater_than, self.added_less_than)
+        {
+            return Ok(false);
         }
 
         // Check if directory
         if let Some(is_dir) = self.is_directory
-            && path.is_dir() != is_dir
+            && data.is_dir() != is_dir
         {
             return Ok(false);
         }
@@ -136,10 +291,110 @@ impl Condition {
             }
         }
 
+        // Check content type (directories have none)
+        if self.mime.is_some() || self.mime_type_prefix.is_some() {
+            let Some(detected) = mime::detect_mime(&data) else {
+                return Ok(false);
+            };
+
+            if let Some(ref pattern) = self.mime
+                && !mime::mime_matches(&detected, pattern)
+            {
+                return Ok(false);
+            }
+
+            if let Some(ref prefix) = self.mime_type_prefix
+                && !detected
+                    .to_ascii_lowercase()
+                    .starts_with(&prefix.to_ascii_lowercase())
+            {
+                return Ok(false);
+            }
+        }
+
+        // Check file kind
+        if let Some(kind) = self.kind
+            && (data.is_dir() || FileKind::detect(&data) != Some(kind))
+        {
+            return Ok(false);
+        }
+
+        // Check file content (binary files never match)
+        if self.content_contains.is_some() || self.content_regex.is_some() {
+            let limit = self
+                .content_read_limit
+                .map_or(content::DEFAULT_READ_LIMIT, ByteSize::bytes);
+            let Some(text) = content::read_text(&data, limit, self.content_encoding.as_deref())?
+            else {
+                return Ok(false);
+            };
+
+            if let Some(ref needle) = self.content_contains
+                && !text.contains(needle.as_str())
+            {
+                return Ok(false);
+            }
+
+            if let Some(ref pattern) = self.content_regex {
+                let regex = Regex::new(pattern)?;
+                let Some(caps) = regex.captures(&text) else {
+                    return Ok(false);
+                };
+                insert_named_captures(&regex, &caps, &mut local);
+                local
+                    .content_captures
+                    .extend(regex.capture_names().flatten().map(String::from));
+            }
+        }
+
+        // Check nested groups
+        for sub in &self.all {
+            if !sub.matches_with(path, &mut local)? {
+                return Ok(false);
+            }
+        }
+
+        if !self.any.is_empty() {
+            let mut matches_any = false;
+            for sub in &self.any {
+                if sub.matches_with(path, &mut local)? {
+                    matches_any = true;
+                    break;
+                }
+            }
+            if !matches_any {
+                return Ok(false);
+            }
+        }
+
+        if let Some(ref sub) = self.not
+            && sub.matches_with(path, &mut ctx.scope())?
+        {
+            return Ok(false);
+        }
+
+        ctx.extend(local);
         Ok(true)
     }
 }
 
+/// Check a timestamp's age against optional lower and upper bounds (exclusive)
+fn check_age(timestamp: SystemTime, greater: Option<Age>, less: Option<Age>) -> bool {
+    // Timestamps in the future count as brand new
+    let age = timestamp.elapsed().unwrap_or_default();
+    greater.is_none_or(|min| age > min.duration()) && less.is_none_or(|max| age < max.duration())
+}
+
+/// When a file was added to its folder: as tracked by the watcher, or estimated
+pub(crate) fn added_time(path: &Path, ctx: &MatchContext) -> Option<SystemTime> {
+    ctx.added.or_else(|| {
+        ctx.data_path(path)
+            .symlink_metadata()
+            .and_then(|m| AgeTimestamp::Added.of(&m))
+            .ok()
+    })
+}
+
 fn check_extension(path: &Path, ext: &str) -> bool {
     path.extension()
         .and_then(|e| e.to_str())
@@ -153,10 +408,51 @@ fn check_glob(path: &Path, pattern: &str) -> Result<bool> {
     Ok(glob_pattern.matches(filename))
 }
 
-fn check_regex(path: &Path, pattern: &str) -> Result<bool> {
+/// Path relative to the watch root, with `/` separators on every platform
+fn relative_path_string(path: &Path, ctx: &MatchContext) -> String {
+    let relative = ctx.relative_path(path);
+    let parts: Vec<_> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    parts.join("/")
+}
+
+fn check_path_glob(relative: &str, pattern: &str) -> Result<bool> {
+    let glob_pattern = glob::Pattern::new(pattern)?;
+    // `*` stays within one folder, only `**` crosses folders
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+    Ok(glob_pattern.matches_with(relative, options))
+}
+
+/// Record named groups only, so numbered groups keep referring to `name_regex`
+fn insert_named_captures(regex: &Regex, caps: &regex::Captures, ctx: &mut MatchContext) {
+    for name in regex.capture_names().flatten() {
+        let value = caps.name(name).map(|m| m.as_str()).unwrap_or("");
+        ctx.captures.insert(name.to_string(), value.to_string());
+    }
+}
+
+/// Match the filename against a regex, returning its capture groups on a match
+fn regex_captures(path: &Path, pattern: &str) -> Result<Option<HashMap<String, String>>> {
     let filename = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
     let regex = Regex::new(pattern)?;
-    Ok(regex.is_match(filename))
+    let Some(caps) = regex.captures(filename) else {
+        return Ok(None);
+    };
+
+    let mut captures = HashMap::new();
+    for (i, name) in regex.capture_names().enumerate() {
+        let value = caps.get(i).map(|m| m.as_str()).unwrap_or("").to_string();
+        if let Some(name) = name {
+            captures.insert(name.to_string(), value.clone());
+        }
+        captures.insert(i.to_string(), value);
+    }
+    Ok(Some(captures))
 }
 
 #[cfg(test)]
@@ -200,4 +496,266 @@ mod tests {
         assert!(condition.matches(Path::new("/tmp/.hidden")).unwrap());
         assert!(!condition.matches(Path::new("/tmp/visible")).unwrap());
     }
+
+    #[test]
+    fn test_any_and_not_groups() {
+        // PDF or DOCX, but not named draft*
+        let condition = Condition {
+            any: vec![
+                Condition {
+                    extension: Some("pdf".to_string()),
+                    ..Default::default()
+                },
+                Condition {
+                    extension: Some("docx".to_string()),
+                    ..Default::default()
+                },
+            ],
+            not: Some(Box::new(Condition {
+                name_matches: Some("draft*".to_string()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        assert!(condition.matches(Path::new("/tmp/report.pdf")).unwrap());
+        assert!(condition.matches(Path::new("/tmp/report.docx")).unwrap());
+        assert!(!condition.matches(Path::new("/tmp/report.txt")).unwrap());
+        assert!(
+            !condition
+                .matches(Path::new("/tmp/draft-report.pdf"))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_nested_all_group() {
+        let condition = Condition {
+            is_hidden: Some(false),
+            all: vec![
+                Condition {
+                    name_matches: Some("IMG_*".to_string()),
+                    ..Default::default()
+                },
+                Condition {
+                    any: vec![
+                        Condition {
+                            extension: Some("jpg".to_string()),
+                            ..Default::default()
+                        },
+                        Condition {
+                            extension: Some("heic".to_string()),
+                            ..Default::default()
+                        },
+                    ],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert!(condition.matches(Path::new("/tmp/IMG_0001.heic")).unwrap());
+        assert!(!condition.matches(Path::new("/tmp/IMG_0001.png")).unwrap());
+        assert!(!condition.matches(Path::new("/tmp/photo.jpg")).unwrap());
+    }
+
+    #[test]
+    fn test_mime_and_kind_match() {
+        let temp = tempfile::tempdir().unwrap();
+        let scan = temp.path().join("scan_001");
+        std::fs::write(&scan, b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n").unwrap();
+        let notes = temp.path().join("notes.pdf");
+        std::fs::write(&notes, "not really a pdf").unwrap();
+
+        let pdf = Condition {
+            mime: Some("application/pdf".to_string()),
+            ..Default::default()
+        };
+        assert!(pdf.matches(&scan).unwrap());
+        assert!(!pdf.matches(&notes).unwrap());
+        assert!(!pdf.matches(temp.path()).unwrap());
+
+        let application = Condition {
+            mime_type_prefix: Some("application/".to_string()),
+            ..Default::default()
+        };
+        assert!(application.matches(&scan).unwrap());
+        assert!(!application.matches(&notes).unwrap());
+
+        let document = Condition {
+            kind: Some(FileKind::Document),
+            ..Default::default()
+        };
+        assert!(document.matches(&scan).unwrap());
+        assert!(!document.matches(temp.path()).unwrap());
+    }
+
+    #[test]
+    fn test_size_and_age_units() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("download.iso");
+        std::fs::write(&path, vec![0u8; 2048]).unwrap();
+        let two_hours_ago = SystemTime::now() - Duration::from_secs(2 * 3600);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(two_hours_ago)
+            .unwrap();
+
+        let condition: Condition = toml::from_str(
+            r#"
+            size_greater_than = "1KiB"
+            size_less_than = "2.5KB"
+            age_greater_than = "90m"
+            age_less_than = "3h"
+            "#,
+        )
+        .unwrap();
+        assert!(condition.matches(&path).unwrap());
+
+        let older = Condition {
+            age_days_greater_than: Some("36h".parse().unwrap()),
+            ..Default::default()
+        };
+        assert!(!older.matches(&path).unwrap());
+
+        // The file was just created, even though its mtime is old
+        let recently_added = Condition {
+            age_days_less_than: Some("1m".parse().unwrap()),
+            age_timestamp: Some(AgeTimestamp::Added),
+            ..Default::default()
+        };
+        assert!(recently_added.matches(&path).unwrap());
+    }
+
+    #[test]
+    fn test_added_uses_tracked_time() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("old-download.zip");
+        std::fs::write(&path, "data").unwrap();
+
+        let condition: Condition = toml::from_str("added_days_greater_than = 7").unwrap();
+        // Without tracking the file counts as just added
+        assert!(!condition.matches(&path).unwrap());
+
+        let tracked = SystemTime::now() - Duration::from_secs(8 * 86_400);
+        let mut ctx = MatchContext::default().with_added(Some(tracked));
+        assert!(condition.matches_with(&path, &mut ctx).unwrap());
+
+        let by_timestamp = Condition {
+            age_days_greater_than: Some(Age::days(7)),
+            age_timestamp: Some(AgeTimestamp::Added),
+            ..Default::default()
+        };
+        assert!(by_timestamp.matches_with(&path, &mut ctx).unwrap());
+    }
+
+    #[test]
+    fn test_path_match_relative_to_root() {
+        let csv_exports = Condition {
+            path_matches: Some("**/exports/*.csv".to_string()),
+            ..Default::default()
+        };
+        let mut ctx = MatchContext::with_root(Some(Path::new("/data/inbox")));
+
+        assert!(
+            csv_exports
+                .matches_with(Path::new("/data/inbox/exports/a.csv"), &mut ctx)
+                .unwrap()
+        );
+        assert!(
+            csv_exports
+                .matches_with(Path::new("/data/inbox/acme/exports/a.csv"), &mut ctx)
+                .unwrap()
+        );
+        assert!(
+            !csv_exports
+                .matches_with(Path::new("/data/inbox/exports/old/a.csv"), &mut ctx)
+                .unwrap()
+        );
+        // The root's own name is not part of the relative path
+        let root_named = MatchContext::with_root(Some(Path::new("/data/exports")));
+        assert!(
+            !csv_exports
+                .matches_with(Path::new("/data/exports/a.csv"), &mut root_named.clone())
+                .unwrap()
+        );
+
+        let by_client = Condition {
+            path_regex: Some(r"^clients/(?P<client>[^/]+)/".to_string()),
+            ..Default::default()
+        };
+        assert!(
+            by_client
+                .matches_with(Path::new("/data/inbox/clients/acme/x.pdf"), &mut ctx)
+                .unwrap()
+        );
+        assert_eq!(ctx.captures["client"], "acme");
+    }
+
+    #[test]
+    fn test_content_match() {
+        let temp = tempfile::tempdir().unwrap();
+        let log = temp.path().join("app.log");
+        std::fs::write(&log, "INFO start\nFATAL out of memory\ncustomer: C-1042\n").unwrap();
+
+        let fatal = Condition {
+            content_contains: Some("FATAL".to_string()),
+            ..Default::default()
+        };
+        assert!(fatal.matches(&log).unwrap());
+
+        // Beyond the read limit
+        let limited = Condition {
+            content_read_limit: Some(ByteSize(8)),
+            ..fatal.clone()
+        };
+        assert!(!limited.matches(&log).unwrap());
+
+        let customer = Condition {
+            content_regex: Some(r"customer: (?P<customer>C-\d+)".to_string()),
+            ..Default::default()
+        };
+        let mut ctx = MatchContext::default();
+        assert!(customer.matches_with(&log, &mut ctx).unwrap());
+        assert_eq!(ctx.captures["customer"], "C-1042");
+
+        let binary = temp.path().join("core.dump");
+        std::fs::write(&binary, b"FATAL\0\0\x7f").unwrap();
+        assert!(!fatal.matches(&binary).unwrap());
+    }
+
+    #[test]
+    fn test_regex_captures_recorded_on_match() {
+        let condition = Condition {
+            name_regex: Some(r"^INV-(?P<vendor>\w+)-(?P<no>\d+)\.pdf$".to_string()),
+            ..Default::default()
+        };
+
+        let mut ctx = MatchContext::default();
+        assert!(
+            condition
+                .matches_with(Path::new("/tmp/INV-acme-0042.pdf"), &mut ctx)
+                .unwrap()
+        );
+        assert_eq!(ctx.captures["vendor"], "acme");
+        assert_eq!(ctx.captures["no"], "0042");
+        assert_eq!(ctx.captures["2"], "0042");
+        assert_eq!(ctx.captures["0"], "INV-acme-0042.pdf");
+
+        // A failed match leaves the context untouched, even if a group matched
+        let grouped = Condition {
+            extension: Some("txt".to_string()),
+            all: vec![condition],
+            ..Default::default()
+        };
+        let mut ctx = MatchContext::default();
+        assert!(
+            !grouped
+                .matches_with(Path::new("/tmp/INV-acme-0042.pdf"), &mut ctx)
+                .unwrap()
+        );
+        assert!(ctx.captures.is_empty());
+    }
 }
@@ -0,0 +1,326 @@
+//! Conflict resolution when an action's target path already exists
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Upper bound for `{counter}` before giving up on finding a free name
+const MAX_COUNTER: u32 = 10_000;
+
+/// What to do when the target of a move, copy or rename already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    /// Fail the action (default)
+    #[default]
+    Error,
+    /// Leave both files alone
+    Skip,
+    /// Replace the existing file
+    Overwrite,
+    /// Pick a free name using the conflict pattern, e.g. "report (2).pdf"
+    Rename,
+    /// Replace the existing file only if the incoming one is newer
+    OverwriteIfNewer,
+    /// Replace the existing file only if the incoming one is larger
+    OverwriteIfLarger,
+    /// Delete the incoming file if the existing one has identical content,
+    /// otherwise rename like `rename`
+    Deduplicate,
+}
+
+impl ConflictStrategy {
+    /// Get all strategies
+    pub fn all() -> &'static [ConflictStrategy] {
+        &[
+            Self::Error,
+            Self::Skip,
+            Self::Overwrite,
+            Self::Rename,
+            Self::OverwriteIfNewer,
+            Self::OverwriteIfLarger,
+            Self::Deduplicate,
+        ]
+    }
+
+    /// Config name of the strategy
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Skip => "skip",
+            Self::Overwrite => "overwrite",
+            Self::Rename => "rename",
+            Self::OverwriteIfNewer => "overwrite_if_newer",
+            Self::OverwriteIfLarger => "overwrite_if_larger",
+            Self::Deduplicate => "deduplicate",
+        }
+    }
+
+    /// Strategy to use, taking the legacy `overwrite = true` flag into account
+    pub fn effective(self, overwrite: bool) -> Self {
+        if overwrite && self == Self::Error {
+            Self::Overwrite
+        } else {
+            self
+        }
+    }
+}
+
+impl std::fmt::Display for ConflictStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Outcome of resolving a conflict
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// Write to this path (no conflict, or a renamed free path)
+    Proceed(PathBuf),
+    /// Replace the existing file at this path
+    Overwrite(PathBuf),
+    /// Do nothing, the incoming file stays where it is
+    Skip,
+    /// The target already has identical content, drop the incoming file
+    Duplicate,
+}
+
+/// Decide where `source` should go given the desired `target`
+///
+/// `pattern` is used by `rename` and `deduplicate` to build alternative names;
+/// it must contain `{counter}` and defaults to "{name} ({counter}).{ext}".
+pub fn resolve(
+    strategy: ConflictStrategy,
+    pattern: Option<&str>,
+    source: &Path,
+    target: &Path,
+) -> Result<Resolution> {
+    let resolution = decide(strategy, pattern, source, target)?;
+    if resolution == Resolution::Proceed(target.to_path_buf()) {
+        return Ok(resolution);
+    }
+
+    match &resolution {
+        Resolution::Proceed(path) => info!(
+            "Conflict at {}: using new name {}",
+            target.display(),
+            path.display()
+        ),
+        Resolution::Overwrite(_) => info!(
+            "Conflict at {}: overwriting with {}",
+            target.display(),
+            source.display()
+        ),
+        Resolution::Skip => info!(
+            "Conflict at {}: skipping {}",
+            target.display(),
+            source.display()
+        ),
+        Resolution::Duplicate => info!(
+            "Conflict at {}: {} is an identical duplicate",
+            target.display(),
+            source.display()
+        ),
+    }
+
+    Ok(resolution)
+}
+
+/// Like [`resolve`], but without logging (for plans)
+pub fn decide(
+    strategy: ConflictStrategy,
+    pattern: Option<&str>,
+    source: &Path,
+    target: &Path,
+) -> Result<Resolution> {
+    if target.symlink_metadata().is_err() || target == source {
+        return Ok(Resolution::Proceed(target.to_path_buf()));
+    }
+
+    Ok(match strategy {
+        ConflictStrategy::Error => {
+            anyhow::bail!("Destination already exists: {}", target.display());
+        }
+        ConflictStrategy::Skip => Resolution::Skip,
+        ConflictStrategy::Overwrite => Resolution::Overwrite(target.to_path_buf()),
+        ConflictStrategy::Rename => Resolution::Proceed(free_path(target, pattern)?),
+        ConflictStrategy::OverwriteIfNewer => {
+            let modified = |p: &Path| {
+                p.metadata()
+                    .and_then(|m| m.modified())
+                    .with_context(|| format!("Failed to read mtime of {}", p.display()))
+            };
+            if modified(source)? > modified(target)? {
+                Resolution::Overwrite(target.to_path_buf())
+            } else {
+                Resolution::Skip
+            }
+        }
+        ConflictStrategy::OverwriteIfLarger => {
+            if source.metadata()?.len() > target.metadata()?.len() {
+                Resolution::Overwrite(target.to_path_buf())
+            } else {
+                Resolution::Skip
+            }
+        }
+        ConflictStrategy::Deduplicate => {
+            if same_content(source, target)? {
+                Resolution::Duplicate
+            } else {
+                Resolution::Proceed(free_path(target, pattern)?)
+            }
+        }
+    })
+}
+
+/// Find the first free path by expanding `{counter}` from 2 upwards
+fn free_path(target: &Path, pattern: Option<&str>) -> Result<PathBuf> {
+    let parent = target.parent().unwrap_or(Path::new("."));
+    let file_name = target
+        .file_name()
+        .context("File has no name")?
+        .to_string_lossy()
+        .to_string();
+    let (stem, ext) = match file_name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() && !target.is_dir() => (stem, Some(ext)),
+        _ => (file_name.as_str(), None),
+    };
+
+    let pattern = match (pattern, ext) {
+        (Some(pattern), _) => pattern,
+        (None, Some(_)) => "{name} ({counter}).{ext}",
+        (None, None) => "{name} ({counter})",
+    };
+    if !pattern.contains("{counter}") {
+        anyhow::bail!("Conflict pattern must contain {{counter}}: {}", pattern);
+    }
+
+    for counter in 2..MAX_COUNTER {
+        let name = pattern
+            .replace("{counter}", &counter.to_string())
+            .replace("{name}", stem)
+            .replace("{ext}", ext.unwrap_or(""))
+            .replace("{filename}", &file_name);
+        let candidate = parent.join(name);
+        if candidate.symlink_metadata().is_err() {
+            return Ok(candidate);
+        }
+    }
+
+    anyhow::bail!("No free name found for {}", target.display())
+}
+
+/// Compare two files byte by byte (directories are never duplicates)
+pub(crate) fn same_content(a: &Path, b: &Path) -> Result<bool> {
+    let (meta_a, meta_b) = (a.metadata()?, b.metadata()?);
+    if !meta_a.is_file() || !meta_b.is_file() || meta_a.len() != meta_b.len() {
+        return Ok(false);
+    }
+
+    let mut reader_a = BufReader::new(File::open(a)?);
+    let mut reader_b = BufReader::new(File::open(b)?);
+    let mut buf_a = [0u8; 8192];
+    let mut buf_b = [0u8; 8192];
+    loop {
+        let n = reader_a.read(&mut buf_a)?;
+        if n == 0 {
+            return Ok(true);
+        }
+        reader_b.read_exact(&mut buf_b[..n])?;
+        if buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_strategies() {
+        let temp = tempfile::tempdir().unwrap();
+        let source = temp.path().join("in").join("report.pdf");
+        std::fs::create_dir(source.parent().unwrap()).unwrap();
+        std::fs::write(&source, "new content").unwrap();
+        let target = temp.path().join("report.pdf");
+
+        // No conflict
+        assert_eq!(
+            resolve(ConflictStrategy::Error, None, &source, &target).unwrap(),
+            Resolution::Proceed(target.clone())
+        );
+
+        std::fs::write(&target, "old").unwrap();
+        assert!(resolve(ConflictStrategy::Error, None, &source, &target).is_err());
+        assert_eq!(
+            resolve(ConflictStrategy::Skip, None, &source, &target).unwrap(),
+            Resolution::Skip
+        );
+        assert_eq!(
+            resolve(ConflictStrategy::Overwrite, None, &source, &target).unwrap(),
+            Resolution::Overwrite(target.clone())
+        );
+        assert_eq!(
+            resolve(ConflictStrategy::OverwriteIfLarger, None, &source, &target).unwrap(),
+            Resolution::Overwrite(target.clone())
+        );
+
+        std::fs::write(temp.path().join("report (2).pdf"), "taken").unwrap();
+        assert_eq!(
+            resolve(ConflictStrategy::Rename, None, &source, &target).unwrap(),
+            Resolution::Proceed(temp.path().join("report (3).pdf"))
+        );
+        assert_eq!(
+            resolve(
+                ConflictStrategy::Rename,
+                Some("{name}_{counter}.{ext}"),
+                &source,
+                &target
+            )
+            .unwrap(),
+            Resolution::Proceed(temp.path().join("report_2.pdf"))
+        );
+        assert!(resolve(ConflictStrategy::Rename, Some("{name}"), &source, &target).is_err());
+    }
+
+    #[test]
+    fn test_resolve_deduplicate() {
+        let temp = tempfile::tempdir().unwrap();
+        let source = temp.path().join("in").join("a.txt");
+        std::fs::create_dir(source.parent().unwrap()).unwrap();
+        std::fs::write(&source, "same").unwrap();
+        let target = temp.path().join("a.txt");
+        std::fs::write(&target, "same").unwrap();
+
+        assert_eq!(
+            resolve(ConflictStrategy::Deduplicate, None, &source, &target).unwrap(),
+            Resolution::Duplicate
+        );
+
+        std::fs::write(&target, "diff").unwrap();
+        assert_eq!(
+            resolve(ConflictStrategy::Deduplicate, None, &source, &target).unwrap(),
+            Resolution::Proceed(temp.path().join("a (2).txt"))
+        );
+    }
+
+    #[test]
+    fn test_effective_strategy() {
+        assert_eq!(
+            ConflictStrategy::Error.effective(true),
+            ConflictStrategy::Overwrite
+        );
+        assert_eq!(
+            ConflictStrategy::Skip.effective(true),
+            ConflictStrategy::Skip
+        );
+        assert_eq!(
+            ConflictStrategy::Error.effective(false),
+            ConflictStrategy::Error
+        );
+    }
+}
@@ -0,0 +1,377 @@
+//! Reading a file's text for content conditions
+//!
+//! Plain text is decoded from its byte order mark, as UTF-8, or as
+//! Windows-1252 as a last resort. PDF, DOCX and ODT documents have their text
+//! extracted. Other binary files have no text.
+
+use anyhow::{Context, Result, bail};
+use encoding_rs::{Encoding, WINDOWS_1252};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use tracing::debug;
+
+use super::mime;
+
+/// Bytes of text examined by content conditions unless configured otherwise
+pub const DEFAULT_READ_LIMIT: u64 = 1024 * 1024;
+
+/// PDFs larger than this aren't parsed; their text can't be read in part
+const MAX_PDF_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Bytes of document XML read per byte of text wanted, to allow for markup
+const XML_PER_TEXT_BYTE: u64 = 8;
+
+const PDF: &str = "application/pdf";
+const DOCX: &str = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+const ODT: &str = "application/vnd.oasis.opendocument.text";
+
+/// Read up to `limit` bytes of a file's text
+///
+/// `encoding` is an encoding label such as "utf-16le" or "shift_jis"; when
+/// unset it is detected. Returns `None` for directories, binary files and
+/// documents whose text can't be extracted.
+pub fn read_text(path: &Path, limit: u64, encoding: Option<&str>) -> Result<Option<String>> {
+    let Some(mime) = mime::detect_mime(path) else {
+        return Ok(None);
+    };
+
+    let text = match mime.as_str() {
+        // Sniffing can report a document as a plain zip, so zips are checked too
+        PDF | DOCX | ODT | "application/zip" => match extract_document(path, &mime, limit) {
+            Ok(text) => text,
+            Err(e) => {
+                debug!("No text extracted from {}: {:#}", path.display(), e);
+                return Ok(None);
+            }
+        },
+        // Unrecognized content may still be text in another encoding
+        "application/octet-stream" => return read_plain(path, limit, encoding),
+        m if m.starts_with("text/") => return read_plain(path, limit, encoding),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(truncate(text, limit)))
+}
+
+/// Read and decode the start of a plain text file
+fn read_plain(path: &Path, limit: u64, encoding: Option<&str>) -> Result<Option<String>> {
+    let mut buf = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(limit).read_to_end(&mut buf))
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let truncated = buf.len() as u64 == limit;
+    decode(&buf, encoding, truncated)
+}
+
+/// Decode bytes as text, returning `None` if they look binary
+///
+/// `truncated` means the read limit may have cut the last character in half.
+fn decode(buf: &[u8], encoding: Option<&str>, truncated: bool) -> Result<Option<String>> {
+    if let Some(label) = encoding {
+        let encoding = Encoding::for_label(label.trim().as_bytes())
+            .with_context(|| format!("Unknown content encoding: {}", label))?;
+        return Ok(Some(encoding.decode(buf).0.into_owned()));
+    }
+
+    if let Some((encoding, bom_len)) = Encoding::for_bom(buf) {
+        let text = encoding.decode_without_bom_handling(&buf[bom_len..]).0;
+        return Ok(Some(text.into_owned()));
+    }
+
+    if buf.contains(&0) {
+        return Ok(None);
+    }
+
+    match std::str::from_utf8(buf) {
+        Ok(text) => Ok(Some(text.to_string())),
+        Err(e) if truncated && e.error_len().is_none() => Ok(Some(
+            String::from_utf8_lossy(&buf[..e.valid_up_to()]).into_owned(),
+        )),
+        Err(_) => Ok(Some(
+            WINDOWS_1252.decode_without_bom_handling(buf).0.into_owned(),
+        )),
+    }
+}
+
+/// Extract the text of a PDF, DOCX or ODT document
+fn extract_document(path: &Path, mime: &str, limit: u64) -> Result<String> {
+    match mime {
+        PDF => {
+            let size = std::fs::metadata(path)?.len();
+            if size > MAX_PDF_SIZE {
+                bail!("PDF is {} bytes, larger than {}", size, MAX_PDF_SIZE);
+            }
+            let data = std::fs::read(path)?;
+            // pdf-extract panics on some malformed files
+            std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(&data))
+                .map_err(|_| anyhow::anyhow!("PDF parser panicked"))?
+                .map_err(|e| anyhow::anyhow!("{}", e))
+        }
+        _ => zip_document_text(path, limit),
+    }
+}
+
+/// Text of a DOCX (`word/document.xml`) or ODT (`content.xml`) document
+///
+/// Only the start of the XML is read, enough for about `limit` bytes of text.
+fn zip_document_text(path: &Path, limit: u64) -> Result<String> {
+    let mut zip = zip::ZipArchive::new(File::open(path)?)?;
+    let member = ["word/document.xml", "content.xml"]
+        .into_iter()
+        .find(|name| zip.index_for_name(name).is_some())
+        .context("Not a DOCX or ODT document")?;
+
+    let mut xml = Vec::new();
+    zip.by_name(member)?
+        .take(limit.saturating_mul(XML_PER_TEXT_BYTE))
+        .read_to_end(&mut xml)?;
+    // A cut-off last character is dropped with the rest of the excess
+    Ok(xml_text(&String::from_utf8_lossy(&xml)))
+}
+
+/// Strip XML markup, keeping paragraphs on separate lines
+fn xml_text(xml: &str) -> String {
+    let mut out = String::with_capacity(xml.len() / 4);
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        out.push_str(&decode_entities(&rest[..start]));
+        let Some(end) = rest[start..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        let name = tag
+            .trim_end_matches('/')
+            .split_whitespace()
+            .next()
+            .unwrap_or("");
+        match name {
+            "/w:p" | "/text:p" | "/text:h" | "w:br" | "text:line-break" => out.push('\n'),
+            "w:tab" | "text:tab" | "text:s" => out.push(' '),
+            _ => {}
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(&decode_entities(rest));
+    out
+}
+
+/// Decode the predefined XML entities and numeric character references
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find(';') else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+        let entity = &after[..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse()))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Cut text down to at most `limit` bytes on a character boundary
+fn truncate(mut text: String, limit: u64) -> String {
+    let limit = usize::try_from(limit).unwrap_or(usize::MAX);
+    if text.len() > limit {
+        let mut end = limit;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Build a one-page PDF showing `text` in Helvetica
+    fn minimal_pdf(text: &str) -> Vec<u8> {
+        let stream = format!("BT /F1 12 Tf 72 712 Td ({}) Tj ET", text);
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] \
+             /Resources << /Font << /F1 4 0 R >> >> /Contents 5 0 R >>"
+                .to_string(),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+            format!(
+                "<< /Length {} >>\nstream\n{}\nendstream",
+                stream.len(),
+                stream
+            ),
+        ];
+
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).bytes());
+        }
+        let xref = pdf.len();
+        pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).bytes());
+        for offset in offsets {
+            pdf.extend(format!("{:010} 00000 n \n", offset).bytes());
+        }
+        pdf.extend(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                objects.len() + 1,
+                xref
+            )
+            .bytes(),
+        );
+        pdf
+    }
+
+    #[test]
+    fn test_read_text_encodings() {
+        let temp = tempfile::tempdir().unwrap();
+
+        let utf16 = temp.path().join("utf16.txt");
+        let mut data = vec![0xFF, 0xFE];
+        data.extend("Grüße".encode_utf16().flat_map(|u| u.to_le_bytes()));
+        std::fs::write(&utf16, data).unwrap();
+        assert_eq!(
+            read_text(&utf16, DEFAULT_READ_LIMIT, None)
+                .unwrap()
+                .unwrap(),
+            "Grüße"
+        );
+
+        let latin1 = temp.path().join("latin1.txt");
+        std::fs::write(&latin1, b"caf\xe9").unwrap();
+        assert_eq!(
+            read_text(&latin1, DEFAULT_READ_LIMIT, None)
+                .unwrap()
+                .unwrap(),
+            "café"
+        );
+
+        let sjis = temp.path().join("sjis.txt");
+        std::fs::write(&sjis, b"\x93\xfa\x96\x7b").unwrap();
+        assert_eq!(
+            read_text(&sjis, DEFAULT_READ_LIMIT, Some("shift_jis"))
+                .unwrap()
+                .unwrap(),
+            "日本"
+        );
+        assert!(read_text(&sjis, DEFAULT_READ_LIMIT, Some("klingon")).is_err());
+
+        // Read limit, cutting the 2-byte "é" in half
+        let utf8 = temp.path().join("utf8.txt");
+        std::fs::write(&utf8, "abcé").unwrap();
+        assert_eq!(read_text(&utf8, 4, None).unwrap().unwrap(), "abc");
+    }
+
+    #[test]
+    fn test_read_text_skips_binary() {
+        let temp = tempfile::tempdir().unwrap();
+        let png = temp.path().join("image.png");
+        std::fs::write(&png, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR FATAL").unwrap();
+        assert!(read_text(&png, DEFAULT_READ_LIMIT, None).unwrap().is_none());
+
+        let blob = temp.path().join("blob");
+        std::fs::write(&blob, b"FATAL\0\x01\x02").unwrap();
+        assert!(
+            read_text(&blob, DEFAULT_READ_LIMIT, None)
+                .unwrap()
+                .is_none()
+        );
+
+        assert!(
+            read_text(temp.path(), DEFAULT_READ_LIMIT, None)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_read_text_documents() {
+        let temp = tempfile::tempdir().unwrap();
+
+        let pdf = temp.path().join("invoice.pdf");
+        std::fs::write(&pdf, minimal_pdf("Customer 4711")).unwrap();
+        let text = read_text(&pdf, DEFAULT_READ_LIMIT, None).unwrap().unwrap();
+        assert!(text.contains("Customer 4711"), "got {:?}", text);
+
+        let docx = temp.path().join("letter.docx");
+        let mut zip = zip::ZipWriter::new(File::create(&docx).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("[Content_Types].xml", options).unwrap();
+        zip.write_all(b"<Types/>").unwrap();
+        zip.start_file("word/document.xml", options).unwrap();
+        zip.write_all(
+            b"<w:document><w:body><w:p><w:r><w:t>Dear Tom &amp; Jerry</w:t></w:r></w:p>\
+              <w:p><w:r><w:t>Ref 42</w:t></w:r></w:p></w:body></w:document>",
+        )
+        .unwrap();
+        zip.finish().unwrap();
+        assert_eq!(
+            read_text(&docx, DEFAULT_READ_LIMIT, None).unwrap().unwrap(),
+            "Dear Tom & Jerry\nRef 42\n"
+        );
+    }
+
+    #[test]
+    fn test_document_read_is_bounded() {
+        let temp = tempfile::tempdir().unwrap();
+        let odt = temp.path().join("huge.odt");
+        let mut zip = zip::ZipWriter::new(File::create(&odt).unwrap());
+        zip.start_file("content.xml", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"<office:text><text:p>START</text:p>")
+            .unwrap();
+        for _ in 0..100_000 {
+            zip.write_all(b"<text:p>filler filler filler</text:p>")
+                .unwrap();
+        }
+        zip.write_all(b"<text:p>END</text:p></office:text>")
+            .unwrap();
+        zip.finish().unwrap();
+
+        // Only the start of the member is decompressed
+        let text = zip_document_text(&odt, 64).unwrap();
+        assert!(text.starts_with("START\n"));
+        assert!(text.len() <= 64 * XML_PER_TEXT_BYTE as usize);
+
+        let text = read_text(&odt, 64, None).unwrap().unwrap();
+        assert!(text.starts_with("START") && text.len() <= 64);
+        assert!(!text.contains("END"));
+    }
+}
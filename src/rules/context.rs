@@ -0,0 +1,113 @@
+//! Match context - values gathered while matching a rule, used by its actions
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Values a rule's condition produced for a file
+///
+/// The context is filled while the condition is evaluated and handed to every
+/// action in the rule's pipeline, so patterns can refer to it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MatchContext {
+    /// Regex capture groups from `name_regex`, by name and by number
+    /// (`"0"` is the whole match), plus named groups from `path_regex` and
+    /// `content_regex`. Groups that didn't participate are empty.
+    pub captures: HashMap<String, String>,
+
+    /// Names of the captures taken from file content (`content_regex`)
+    ///
+    /// Anyone who can get a file into a watched folder controls these, so
+    /// they are checked before going into a path or a shell command.
+    pub content_captures: HashSet<String>,
+
+    /// Root of the watch the file was found in
+    pub root: Option<PathBuf>,
+
+    /// When the watcher first saw the file in its folder
+    pub added: Option<SystemTime>,
+
+    /// Where the file's content is read from, if not the path being matched
+    ///
+    /// Set while planning: later rules are checked against the path an earlier
+    /// rule would move the file to, but its data is still at the original path.
+    pub data: Option<PathBuf>,
+}
+
+impl MatchContext {
+    /// Create a context for a file found under a watch root
+    pub fn with_root(root: Option<&Path>) -> Self {
+        Self {
+            root: root.map(Path::to_path_buf),
+            ..Default::default()
+        }
+    }
+
+    /// Set when the file was added to its folder
+    pub fn with_added(mut self, added: Option<SystemTime>) -> Self {
+        self.added = added;
+        self
+    }
+
+    /// Context for evaluating a nested condition: same file, no captures
+    pub fn scope(&self) -> Self {
+        Self {
+            captures: HashMap::new(),
+            content_captures: HashSet::new(),
+            root: self.root.clone(),
+            added: self.added,
+            data: self.data.clone(),
+        }
+    }
+
+    /// Merge captures from another context, overwriting existing keys
+    pub fn extend(&mut self, other: MatchContext) {
+        for name in other.captures.keys() {
+            if !other.content_captures.contains(name) {
+                self.content_captures.remove(name);
+            }
+        }
+        self.content_captures.extend(other.content_captures);
+        self.captures.extend(other.captures);
+    }
+
+    /// Path to read a file's metadata and content from
+    pub fn data_path<'a>(&'a self, path: &'a Path) -> &'a Path {
+        self.data.as_deref().unwrap_or(path)
+    }
+
+    /// Path of a file relative to the watch root
+    ///
+    /// Files outside the root (or without one) are relative to their own
+    /// folder, i.e. just the file name.
+    pub fn relative_path(&self, path: &Path) -> PathBuf {
+        self.root
+            .as_deref()
+            .and_then(|root| path.strip_prefix(root).ok())
+            .filter(|rel| !rel.as_os_str().is_empty())
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| path.file_name().map(PathBuf::from).unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_path() {
+        let ctx = MatchContext::with_root(Some(Path::new("/data/inbox")));
+        assert_eq!(
+            ctx.relative_path(Path::new("/data/inbox/exports/2024/a.csv")),
+            Path::new("exports/2024/a.csv")
+        );
+        assert_eq!(
+            ctx.relative_path(Path::new("/elsewhere/a.csv")),
+            Path::new("a.csv")
+        );
+        assert_eq!(
+            MatchContext::default().relative_path(Path::new("/data/inbox/a.csv")),
+            Path::new("a.csv")
+        );
+    }
+}
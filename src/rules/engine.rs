@@ -1,51 +1,375 @@
 //! Rule engine - evaluates and executes rules
 
 use anyhow::Result;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tracing::{debug, info, trace};
 
-use super::{Action, Rule};
+use super::plan::ConflictOutcome;
+use super::{Action, FilePlan, MatchContext, PlannedFailure, PlannedStep, Rule};
 
 /// Engine for evaluating rules against files
 pub struct RuleEngine {
     rules: Vec<Rule>,
 }
 
+/// A rule whose action was executed on a file
+#[derive(Debug, Clone)]
+pub struct AppliedRule {
+    /// Name of the rule
+    pub rule: String,
+
+    /// Index of the action in the rule's pipeline
+    pub step: usize,
+
+    /// Action that ran
+    pub action: Action,
+
+    /// Path the action ran on
+    pub path: PathBuf,
+
+    /// Location of the file afterwards (None if it no longer exists)
+    pub result: Option<PathBuf>,
+
+    /// File or folder the action made besides it (a copy, an archive, an
+    /// extracted folder, the file in the trash)
+    pub created: Option<PathBuf>,
+}
+
+/// A rule whose conditions or action failed
+#[derive(Debug)]
+pub struct RuleFailure {
+    /// Name of the rule
+    pub rule: String,
+
+    /// Index of the failing action in the rule's pipeline
+    pub step: usize,
+
+    /// The action that failed (None if checking the conditions failed)
+    pub action: Option<Action>,
+
+    /// Path the action ran on
+    pub path: PathBuf,
+
+    /// The error returned by the action
+    pub error: anyhow::Error,
+}
+
+impl std::fmt::Display for RuleFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.action {
+            Some(ref action) => write!(
+                f,
+                "Rule '{}' failed at step {} ({}) for {}: {}",
+                self.rule,
+                self.step + 1,
+                action.name(),
+                self.path.display(),
+                self.error
+            ),
+            None => write!(
+                f,
+                "Rule '{}' failed checking conditions for {}: {}",
+                self.rule,
+                self.path.display(),
+                self.error
+            ),
+        }
+    }
+}
+
+/// Everything that happened while processing a file
+#[derive(Debug, Default)]
+pub struct ProcessReport {
+    /// Rules that ran, in order
+    pub applied: Vec<AppliedRule>,
+
+    /// Rule step that failed, if any (processing stops there)
+    pub failure: Option<RuleFailure>,
+}
+
+impl ProcessReport {
+    /// Whether any rule ran or failed
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.failure.is_none()
+    }
+}
+
 impl RuleEngine {
     /// Create a new rule engine with the given rules
     pub fn new(rules: Vec<Rule>) -> Self {
         Self { rules }
     }
 
-    /// Evaluate rules for a file and return the first matching action
-    pub fn evaluate(&self, path: &Path) -> Result<Option<Action>> {
+    /// Evaluate rules for a file and return every matching rule, in order
+    ///
+    /// Stops after the first matching rule with `stop_processing` set. This only
+    /// looks at the file where it is now; rules that would match after an earlier
+    /// rule moved or renamed it are only found by [`RuleEngine::process`].
+    pub fn evaluate(&self, path: &Path) -> Result<Vec<&Rule>> {
+        self.evaluate_with(path, &MatchContext::default(), |_| true)
+    }
+
+    /// Like [`RuleEngine::evaluate`], but only considers rules accepted by `allow`
+    ///
+    /// `base` describes where the file was found (watch root, added date); each
+    /// rule starts from a copy of it.
+    pub fn evaluate_with(
+        &self,
+        path: &Path,
+        base: &MatchContext,
+        allow: impl Fn(&Rule) -> bool,
+    ) -> Result<Vec<&Rule>> {
         debug!("Evaluating rules for: {}", path.display());
 
+        let mut matched = Vec::new();
+
         for rule in &self.rules {
             if !rule.enabled {
                 trace!("Skipping disabled rule: {}", rule.name);
                 continue;
             }
 
-            if rule.condition.matches(path)? {
+            if !allow(rule) {
+                trace!("Rule '{}' does not apply here", rule.name);
+                continue;
+            }
+
+            if rule.condition.matches_with(path, &mut base.scope())? {
                 info!("Rule '{}' matched: {}", rule.name, path.display());
-                return Ok(Some(rule.action.clone()));
+                matched.push(rule);
+                if rule.stop_processing {
+                    debug!("Rule '{}' stops processing", rule.name);
+                    break;
+                }
             } else {
                 debug!("Rule '{}' did not match: {}", rule.name, path.display());
             }
         }
 
-        debug!("No rules matched for: {}", path.display());
-        Ok(None)
+        if matched.is_empty() {
+            debug!("No rules matched for: {}", path.display());
+        }
+        Ok(matched)
     }
 
-    /// Evaluate rules and execute the matching action
-    pub fn process(&self, path: &Path) -> Result<bool> {
-        if let Some(action) = self.evaluate(path)? {
-            action.execute(path)?;
-            return Ok(true);
+    /// Apply every matching rule to a file, in order
+    ///
+    /// Each rule is checked against the file's current location, so rules after a
+    /// move or rename see the new path. Processing ends when a rule with
+    /// `stop_processing` runs, the file no longer exists, or an action fails.
+    pub fn process(&self, path: &Path) -> Result<ProcessReport> {
+        self.process_with(path, &MatchContext::default(), |_| true)
+    }
+
+    /// Like [`RuleEngine::process`], but only applies rules accepted by `allow`
+    ///
+    /// `base` describes where the file was found (watch root, added date); each
+    /// rule starts from a copy of it.
+    pub fn process_with(
+        &self,
+        path: &Path,
+        base: &MatchContext,
+        allow: impl Fn(&Rule) -> bool,
+    ) -> Result<ProcessReport> {
+        debug!("Processing rules for: {}", path.display());
+
+        let mut report = ProcessReport::default();
+        let mut current = path.to_path_buf();
+
+        for rule in &self.rules {
+            if !rule.enabled {
+                trace!("Skipping disabled rule: {}", rule.name);
+                continue;
+            }
+
+            if !allow(rule) {
+                trace!("Rule '{}' does not apply here", rule.name);
+                continue;
+            }
+
+            // A condition error ends processing like a failed action, keeping
+            // what earlier rules already did in the report
+            let mut ctx = base.scope();
+            match rule.condition.matches_with(&current, &mut ctx) {
+                Ok(true) => {}
+                Ok(false) => {
+                    debug!("Rule '{}' did not match: {}", rule.name, current.display());
+                    continue;
+                }
+                Err(error) => {
+                    report.failure = Some(RuleFailure {
+                        rule: rule.name.clone(),
+                        step: 0,
+                        action: None,
+                        path: current,
+                        error,
+                    });
+                    return Ok(report);
+                }
+            }
+
+            info!("Rule '{}' matched: {}", rule.name, current.display());
+
+            // Run the pipeline, each step on the path the previous one produced
+            let mut step_path = Some(current.clone());
+            for (step, action) in rule.actions.iter().enumerate() {
+                let Some(input) = step_path.take() else {
+                    debug!(
+                        "Rule '{}': file is gone, skipping remaining steps",
+                        rule.name
+                    );
+                    break;
+                };
+
+                match action.perform_with(&input, &ctx) {
+                    Ok(output) => {
+                        report.applied.push(AppliedRule {
+                            rule: rule.name.clone(),
+                            step,
+                            action: action.clone(),
+                            path: input,
+                            result: output.path.clone(),
+                            created: output.created,
+                        });
+                        step_path = output.path;
+                    }
+                    Err(error) => {
+                        report.failure = Some(RuleFailure {
+                            rule: rule.name.clone(),
+                            step,
+                            action: Some(action.clone()),
+                            path: input,
+                            error,
+                        });
+                        return Ok(report);
+                    }
+                }
+            }
+
+            if rule.stop_processing {
+                debug!("Rule '{}' stops processing", rule.name);
+                break;
+            }
+
+            match step_path {
+                Some(next) => current = next,
+                None => break,
+            }
+        }
+
+        if report.is_empty() {
+            debug!("No rules matched for: {}", path.display());
+        }
+        Ok(report)
+    }
+
+    /// Work out what [`RuleEngine::process`] would do to a file, without doing it
+    ///
+    /// Nothing on disk is changed. Later rules are checked against the path an
+    /// earlier rule would move the file to, reading its content and metadata
+    /// from where it is now.
+    pub fn plan(&self, path: &Path) -> Result<FilePlan> {
+        self.plan_with(path, &MatchContext::default(), |_| true)
+    }
+
+    /// Like [`RuleEngine::plan`], but only considers rules accepted by `allow`
+    pub fn plan_with(
+        &self,
+        path: &Path,
+        base: &MatchContext,
+        allow: impl Fn(&Rule) -> bool,
+    ) -> Result<FilePlan> {
+        debug!("Planning rules for: {}", path.display());
+
+        let mut plan = FilePlan {
+            path: path.to_path_buf(),
+            ..Default::default()
+        };
+        let base = MatchContext {
+            data: Some(path.to_path_buf()),
+            ..base.clone()
+        };
+        let mut current = path.to_path_buf();
+
+        for rule in &self.rules {
+            if !rule.enabled || !allow(rule) {
+                continue;
+            }
+
+            let mut ctx = base.scope();
+            match rule.condition.matches_with(&current, &mut ctx) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(error) => {
+                    plan.failure = Some(PlannedFailure {
+                        rule: rule.name.clone(),
+                        step: 0,
+                        action: None,
+                        error: error.to_string(),
+                    });
+                    return Ok(plan);
+                }
+            }
+
+            let mut step_path = Some(current.clone());
+            for (step, action) in rule.actions.iter().enumerate() {
+                let Some(input) = step_path.take() else {
+                    break;
+                };
+
+                let prediction = match action.plan_with(&input, &ctx) {
+                    Ok(prediction) => prediction,
+                    Err(error) => {
+                        plan.failure = Some(PlannedFailure {
+                            rule: rule.name.clone(),
+                            step,
+                            action: Some(action.name()),
+                            error: error.to_string(),
+                        });
+                        return Ok(plan);
+                    }
+                };
+
+                let blocked = prediction
+                    .conflict
+                    .as_ref()
+                    .filter(|c| c.outcome == ConflictOutcome::Error)
+                    .map(|c| c.existing.clone());
+                step_path = prediction.output.path.clone();
+                plan.steps.push(PlannedStep {
+                    rule: rule.name.clone(),
+                    step,
+                    action: action.name(),
+                    source: input,
+                    result: prediction.output.path,
+                    created: prediction.output.created,
+                    command: prediction.command,
+                    conflict: prediction.conflict,
+                    warnings: prediction.warnings,
+                });
+
+                if let Some(existing) = blocked {
+                    plan.failure = Some(PlannedFailure {
+                        rule: rule.name.clone(),
+                        step,
+                        action: Some(action.name()),
+                        error: format!("Destination already exists: {}", existing.display()),
+                    });
+                    return Ok(plan);
+                }
+            }
+
+            if rule.stop_processing {
+                break;
+            }
+
+            match step_path {
+                Some(next) => current = next,
+                None => break,
+            }
         }
-        Ok(false)
+
+        Ok(plan)
     }
 
     /// Get all rules
@@ -76,7 +400,7 @@ impl RuleEngine {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::rules::Condition;
+    use crate::rules::{Condition, ConflictStrategy};
     use std::path::PathBuf;
 
     #[test]
@@ -91,16 +415,18 @@ mod tests {
                 destination: PathBuf::from("/tmp/pdfs"),
                 create_destination: true,
                 overwrite: false,
+                conflict: Default::default(),
+                conflict_pattern: None,
             },
         )];
 
         let engine = RuleEngine::new(rules);
 
         let result = engine.evaluate(Path::new("/tmp/test.pdf")).unwrap();
-        assert!(result.is_some());
+        assert_eq!(result.len(), 1);
 
         let result = engine.evaluate(Path::new("/tmp/test.txt")).unwrap();
-        assert!(result.is_none());
+        assert!(result.is_empty());
     }
 
     #[test]
@@ -112,13 +438,362 @@ mod tests {
                 extension: Some("pdf".to_string()),
                 ..Default::default()
             },
-            action: Action::Delete,
+            actions: vec![Action::Delete],
             stop_processing: false,
+            rescan: None,
         }];
 
         let engine = RuleEngine::new(rules);
 
         let result = engine.evaluate(Path::new("/tmp/test.pdf")).unwrap();
-        assert!(result.is_none());
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn test_evaluate_honors_stop_processing() {
+        let pdf = || Condition {
+            extension: Some("pdf".to_string()),
+            ..Default::default()
+        };
+        let mut first = Rule::new("First", pdf(), Action::Nothing);
+        first.stop_processing = true;
+        let rules = vec![
+            Rule::new("Zero", pdf(), Action::Nothing),
+            first,
+            Rule::new("Never", pdf(), Action::Nothing),
+        ];
+
+        let engine = RuleEngine::new(rules);
+        let names: Vec<_> = engine
+            .evaluate(Path::new("/tmp/test.pdf"))
+            .unwrap()
+            .iter()
+            .map(|r| r.name.as_str())
+            .collect();
+        assert_eq!(names, ["Zero", "First"]);
+    }
+
+    #[test]
+    fn test_process_follows_renamed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("report.txt");
+        std::fs::write(&file, "data").unwrap();
+
+        let archive = dir.path().join("archive");
+        let rules = vec![
+            Rule::new(
+                "Rename",
+                Condition {
+                    extension: Some("txt".to_string()),
+                    ..Default::default()
+                },
+                Action::Rename {
+                    pattern: "{name}.md".to_string(),
+                    conflict: Default::default(),
+                    conflict_pattern: None,
+                },
+            ),
+            Rule::new(
+                "Move markdown",
+                Condition {
+                    extension: Some("md".to_string()),
+                    ..Default::default()
+                },
+                Action::Move {
+                    destination: archive.clone(),
+                    create_destination: true,
+                    overwrite: false,
+                    conflict: Default::default(),
+                    conflict_pattern: None,
+                },
+            ),
+        ];
+
+        let engine = RuleEngine::new(rules);
+        let report = engine.process(&file).unwrap();
+
+        assert!(report.failure.is_none());
+        assert_eq!(report.applied.len(), 2);
+        assert_eq!(report.applied[1].path, dir.path().join("report.md"));
+        assert_eq!(report.applied[1].result, Some(archive.join("report.md")));
+        assert!(archive.join("report.md").exists());
+    }
+
+    #[test]
+    fn test_process_with_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("setup.dmg");
+        std::fs::write(&file, "data").unwrap();
+
+        let rules = vec![Rule::new(
+            "Delete installers",
+            Condition {
+                extension: Some("dmg".to_string()),
+                ..Default::default()
+            },
+            Action::Delete,
+        )];
+
+        let engine = RuleEngine::new(rules);
+
+        let report = engine
+            .process_with(&file, &MatchContext::default(), |rule| {
+                rule.name != "Delete installers"
+            })
+            .unwrap();
+        assert!(report.is_empty());
+        assert!(file.exists());
+
+        let report = engine.process(&file).unwrap();
+        assert_eq!(report.applied.len(), 1);
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn test_process_runs_pipeline_and_reports_failing_step() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("report.txt");
+        std::fs::write(&file, "data").unwrap();
+        let archive = dir.path().join("archive");
+
+        let mut rule = Rule::new(
+            "Pipeline",
+            Condition {
+                extension: Some("txt".to_string()),
+                ..Default::default()
+            },
+            Action::Rename {
+                pattern: "final_{filename}".to_string(),
+                conflict: Default::default(),
+                conflict_pattern: None,
+            },
+        );
+        rule.actions.push(Action::Move {
+            destination: archive.clone(),
+            create_destination: true,
+            overwrite: false,
+            conflict: Default::default(),
+            conflict_pattern: None,
+        });
+        rule.actions.push(Action::Run {
+            command: "false".to_string(),
+            args: Vec::new(),
+        });
+
+        let engine = RuleEngine::new(vec![rule]);
+        let report = engine.process(&file).unwrap();
+
+        assert_eq!(report.applied.len(), 2);
+        assert_eq!(report.applied[1].path, dir.path().join("final_report.txt"));
+        assert!(archive.join("final_report.txt").exists());
+
+        let failure = report.failure.unwrap();
+        assert_eq!(failure.step, 2);
+        assert_eq!(failure.action.map(|a| a.name()), Some("run"));
+        assert_eq!(failure.path, archive.join("final_report.txt"));
+    }
+
+    #[test]
+    fn test_condition_error_keeps_earlier_actions() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        std::fs::write(&file, "data").unwrap();
+        let archive = dir.path().join("archive");
+
+        let rules = vec![
+            Rule::new(
+                "Archive",
+                Condition::default(),
+                Action::Move {
+                    destination: archive.clone(),
+                    create_destination: true,
+                    overwrite: false,
+                    conflict: Default::default(),
+                    conflict_pattern: None,
+                },
+            ),
+            Rule::new(
+                "Broken",
+                Condition {
+                    content_contains: Some("data".to_string()),
+                    content_encoding: Some("klingon".to_string()),
+                    ..Default::default()
+                },
+                Action::Delete,
+            ),
+        ];
+        let engine = RuleEngine::new(rules);
+
+        // The move is reported so it can be journaled and undone
+        let report = engine.process(&file).unwrap();
+        assert_eq!(report.applied.len(), 1);
+        assert_eq!(report.applied[0].result, Some(archive.join("notes.txt")));
+        let failure = report.failure.unwrap();
+        assert_eq!(failure.rule, "Broken");
+        assert!(failure.action.is_none());
+        assert_eq!(failure.path, archive.join("notes.txt"));
+        assert!(failure.to_string().contains("checking conditions"));
+        assert!(archive.join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_process_uses_regex_captures() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("INV-acme-0042.pdf");
+        std::fs::write(&file, "pdf").unwrap();
+        let invoices = dir.path().join("Invoices");
+
+        let rules = vec![Rule::new(
+            "Invoices",
+            Condition {
+                name_regex: Some(r"^INV-(?P<vendor>\w+)-(?P<no>\d+)\.pdf$".to_string()),
+                ..Default::default()
+            },
+            Action::Move {
+                destination: invoices.join("{re:vendor}"),
+                create_destination: true,
+                overwrite: false,
+                conflict: Default::default(),
+                conflict_pattern: None,
+            },
+        )];
+
+        let report = RuleEngine::new(rules).process(&file).unwrap();
+        assert!(report.failure.is_none());
+        assert!(invoices.join("acme").join("INV-acme-0042.pdf").exists());
+    }
+
+    #[test]
+    fn test_content_captures_cannot_escape_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let invoices = dir.path().join("Invoices");
+        let rule = |pattern: &str| {
+            Rule::new(
+                "Invoices",
+                Condition {
+                    content_regex: Some(r"customer: (?P<customer>\S+)".to_string()),
+                    ..Default::default()
+                },
+                Action::Move {
+                    destination: invoices.join(pattern),
+                    create_destination: true,
+                    overwrite: false,
+                    conflict: Default::default(),
+                    conflict_pattern: None,
+                },
+            )
+        };
+
+        // A downloaded file naming a path outside the tree is refused
+        let file = dir.path().join("invoice.txt");
+        std::fs::write(&file, "customer: ../../.ssh/x\n").unwrap();
+        let engine = RuleEngine::new(vec![rule("{re:customer}")]);
+        let report = engine.process(&file).unwrap();
+        assert!(report.applied.is_empty());
+        let failure = report.failure.unwrap();
+        assert!(failure.error.to_string().contains("file content"));
+        assert!(file.exists());
+        assert!(!dir.path().join(".ssh").exists());
+
+        // A filter can make the value safe
+        let engine = RuleEngine::new(vec![rule("{re:customer|slug}")]);
+        let report = engine.process(&file).unwrap();
+        assert!(report.failure.is_none());
+        assert_eq!(
+            report.applied[0].result.as_ref().unwrap().parent(),
+            Some(invoices.join("ssh-x").as_path())
+        );
+    }
+
+    #[test]
+    fn test_plan_predicts_without_changing_anything() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("report.txt");
+        std::fs::write(&file, "quarterly data").unwrap();
+        let archive = dir.path().join("archive");
+        std::fs::create_dir(&archive).unwrap();
+        std::fs::write(archive.join("report.md"), "older").unwrap();
+        let missing = dir.path().join("missing");
+
+        let rules = vec![
+            Rule::new(
+                "Rename",
+                Condition {
+                    extension: Some("txt".to_string()),
+                    ..Default::default()
+                },
+                Action::Rename {
+                    pattern: "{name}.md".to_string(),
+                    conflict: Default::default(),
+                    conflict_pattern: None,
+                },
+            ),
+            // Only matches the renamed file, and reads content it doesn't have yet
+            Rule::new(
+                "Archive",
+                Condition {
+                    extension: Some("md".to_string()),
+                    content_contains: Some("quarterly".to_string()),
+                    ..Default::default()
+                },
+                Action::Move {
+                    destination: archive.clone(),
+                    create_destination: true,
+                    overwrite: false,
+                    conflict: ConflictStrategy::Rename,
+                    conflict_pattern: None,
+                },
+            ),
+            Rule::new(
+                "Backup",
+                Condition::default(),
+                Action::Copy {
+                    destination: missing.clone(),
+                    create_destination: false,
+                    overwrite: false,
+                    conflict: Default::default(),
+                    conflict_pattern: None,
+                },
+            ),
+        ];
+
+        let plan = RuleEngine::new(rules).plan(&file).unwrap();
+        assert!(plan.failure.is_none());
+        assert_eq!(plan.rules(), ["Rename", "Archive", "Backup"]);
+        assert_eq!(plan.steps[0].result, Some(dir.path().join("report.md")));
+
+        let moved = archive.join("report (2).md");
+        assert_eq!(plan.steps[1].result, Some(moved.clone()));
+        assert_eq!(
+            plan.steps[1].conflict.as_ref().map(|c| c.outcome),
+            Some(ConflictOutcome::Rename)
+        );
+        assert_eq!(plan.steps[2].source, moved);
+        assert_eq!(plan.steps[2].created, Some(missing.join("report (2).md")));
+        assert_eq!(plan.steps[2].warnings.len(), 1);
+
+        // Nothing happened on disk
+        assert!(file.exists());
+        assert!(!dir.path().join("report.md").exists());
+        assert!(!moved.exists());
+        assert!(!missing.exists());
+
+        // A conflict that would fail ends the plan
+        std::fs::write(dir.path().join("report.md"), "taken").unwrap();
+        let engine = RuleEngine::new(vec![Rule::new(
+            "Rename",
+            Condition::default(),
+            Action::Rename {
+                pattern: "{name}.md".to_string(),
+                conflict: Default::default(),
+                conflict_pattern: None,
+            },
+        )]);
+        let plan = engine.plan(&file).unwrap();
+        assert_eq!(plan.steps.len(), 1);
+        assert_eq!(plan.steps[0].result, Some(file.clone()));
+        let failure = plan.failure.unwrap();
+        assert_eq!(failure.action, Some("rename"));
+        assert!(failure.error.contains("Destination already exists"));
     }
 }
@@ -0,0 +1,228 @@
+//! Content type sniffing and the built-in file kind taxonomy
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Bytes read from the start of a file to detect its type
+const SNIFF_LEN: u64 = 8192;
+
+/// Broad category of a file, detected from its content and extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileKind {
+    Image,
+    Video,
+    Audio,
+    Document,
+    Archive,
+    Code,
+    Font,
+    DiskImage,
+}
+
+impl FileKind {
+    /// Config name of the kind
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Image => "image",
+            Self::Video => "video",
+            Self::Audio => "audio",
+            Self::Document => "document",
+            Self::Archive => "archive",
+            Self::Code => "code",
+            Self::Font => "font",
+            Self::DiskImage => "disk-image",
+        }
+    }
+
+    /// Detect the kind of a file: magic bytes first, then the extension
+    pub fn detect(path: &Path) -> Option<Self> {
+        detect_mime(path)
+            .and_then(|mime| Self::from_mime(&mime))
+            .or_else(|| {
+                path.extension()
+                    .and_then(|e| e.to_str())
+                    .and_then(Self::from_extension)
+            })
+    }
+
+    /// Kind for a MIME type, if it belongs to one
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        let mime = mime.to_ascii_lowercase();
+        let kind = match mime.as_str() {
+            m if m.starts_with("image/") => Self::Image,
+            m if m.starts_with("video/") => Self::Video,
+            m if m.starts_with("audio/") => Self::Audio,
+            m if m.starts_with("font/") || m.starts_with("application/font-") => Self::Font,
+            m if m.starts_with("application/vnd.openxmlformats-officedocument.")
+                || m.starts_with("application/vnd.oasis.opendocument.") =>
+            {
+                Self::Document
+            }
+            "application/pdf"
+            | "application/msword"
+            | "application/vnd.ms-excel"
+            | "application/vnd.ms-powerpoint"
+            | "application/rtf"
+            | "application/epub+zip"
+            | "application/x-mobipocket-ebook"
+            | "application/postscript" => Self::Document,
+            "application/zip"
+            | "application/x-tar"
+            | "application/vnd.rar"
+            | "application/gzip"
+            | "application/x-bzip2"
+            | "application/vnd.bzip3"
+            | "application/x-7z-compressed"
+            | "application/x-xz"
+            | "application/zstd"
+            | "application/x-lz4"
+            | "application/x-lzip"
+            | "application/x-compress"
+            | "application/x-cpio"
+            | "application/vnd.ms-cab-compressed"
+            | "application/x-unix-archive" => Self::Archive,
+            "application/x-apple-diskimage"
+            | "application/x-iso9660-image"
+            | "application/x-raw-disk-image" => Self::DiskImage,
+            "text/html" | "text/xml" | "text/x-shellscript" => Self::Code,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// Kind for a file extension, if it belongs to one
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        let ext = ext.to_ascii_lowercase();
+        let kind = match ext.as_str() {
+            "jpg" | "jpeg" | "png" | "gif" | "webp" | "bmp" | "tif" | "tiff" | "heic" | "heif"
+            | "avif" | "svg" | "ico" | "psd" | "raw" | "cr2" | "nef" | "arw" | "dng" => Self::Image,
+            "mp4" | "m4v" | "mkv" | "webm" | "mov" | "avi" | "wmv" | "mpg" | "mpeg" | "flv" => {
+                Self::Video
+            }
+            "mp3" | "m4a" | "aac" | "ogg" | "opus" | "flac" | "wav" | "aiff" | "mid" | "midi" => {
+                Self::Audio
+            }
+            "pdf" | "doc" | "docx" | "odt" | "rtf" | "txt" | "md" | "pages" | "xls" | "xlsx"
+            | "ods" | "csv" | "numbers" | "ppt" | "pptx" | "odp" | "key" | "epub" | "mobi" => {
+                Self::Document
+            }
+            "zip" | "tar" | "gz" | "tgz" | "bz2" | "xz" | "txz" | "zst" | "tzst" | "7z" | "rar"
+            | "lz4" | "cab" => Self::Archive,
+            "rs" | "py" | "js" | "mjs" | "ts" | "tsx" | "jsx" | "go" | "c" | "h" | "cc" | "cpp"
+            | "hpp" | "java" | "kt" | "swift" | "rb" | "php" | "cs" | "sh" | "bash" | "zsh"
+            | "fish" | "lua" | "pl" | "sql" | "html" | "css" | "scss" | "json" | "toml"
+            | "yaml" | "yml" | "xml" => Self::Code,
+            "ttf" | "otf" | "woff" | "woff2" | "eot" => Self::Font,
+            "iso" | "dmg" | "img" | "vhd" | "vhdx" | "vmdk" | "qcow2" => Self::DiskImage,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
+
+impl std::fmt::Display for FileKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Detect a file's MIME type from its magic bytes
+///
+/// Returns `text/plain` for unrecognized UTF-8 text, `application/octet-stream`
+/// for other unrecognized content, and `None` for directories and unreadable files.
+pub fn detect_mime(path: &Path) -> Option<String> {
+    if !path.is_file() {
+        return None;
+    }
+
+    let mut buf = Vec::with_capacity(SNIFF_LEN as usize);
+    File::open(path)
+        .ok()?
+        .take(SNIFF_LEN)
+        .read_to_end(&mut buf)
+        .ok()?;
+
+    if let Some(kind) = infer::get(&buf) {
+        return Some(kind.mime_type().to_string());
+    }
+
+    if looks_like_text(&buf) {
+        Some("text/plain".to_string())
+    } else {
+        Some("application/octet-stream".to_string())
+    }
+}
+
+/// Check a MIME type against a pattern like `application/pdf` or `image/*`
+pub fn mime_matches(mime: &str, pattern: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => mime
+            .to_ascii_lowercase()
+            .starts_with(&prefix.to_ascii_lowercase()),
+        None => mime.eq_ignore_ascii_case(pattern),
+    }
+}
+
+/// Whether a buffer looks like UTF-8 text (no NUL bytes, valid apart from a cut-off tail)
+pub fn looks_like_text(buf: &[u8]) -> bool {
+    if buf.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(buf) {
+        Ok(_) => true,
+        // error_len() is None when the buffer just ends mid-character
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    const PDF: &[u8] = b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n";
+
+    #[test]
+    fn test_detect_mime_ignores_extension() {
+        let temp = tempfile::tempdir().unwrap();
+
+        let download = temp.path().join("download");
+        std::fs::write(&download, PDF).unwrap();
+        assert_eq!(detect_mime(&download).as_deref(), Some("application/pdf"));
+        assert_eq!(FileKind::detect(&download), Some(FileKind::Document));
+
+        let bin = temp.path().join("photo.bin");
+        std::fs::write(&bin, PNG).unwrap();
+        assert_eq!(detect_mime(&bin).as_deref(), Some("image/png"));
+        assert_eq!(FileKind::detect(&bin), Some(FileKind::Image));
+
+        let code = temp.path().join("main.rs");
+        std::fs::write(&code, "fn main() {}\n").unwrap();
+        assert_eq!(detect_mime(&code).as_deref(), Some("text/plain"));
+        assert_eq!(FileKind::detect(&code), Some(FileKind::Code));
+
+        assert_eq!(detect_mime(temp.path()), None);
+    }
+
+    #[test]
+    fn test_mime_matches() {
+        assert!(mime_matches("image/png", "image/*"));
+        assert!(mime_matches("application/pdf", "application/pdf"));
+        assert!(mime_matches("application/pdf", "*"));
+        assert!(!mime_matches("application/pdf", "image/*"));
+    }
+
+    #[test]
+    fn test_parse_kind() {
+        #[derive(Deserialize)]
+        struct Wrapper {
+            kind: FileKind,
+        }
+        let parsed: Wrapper = toml::from_str(r#"kind = "disk-image""#).unwrap();
+        assert_eq!(parsed.kind, FileKind::DiskImage);
+        assert_eq!(FileKind::from_extension("ISO"), Some(FileKind::DiskImage));
+    }
+}
@@ -1,35 +1,135 @@
 //! Rule engine - conditions and actions for file organization
 
 mod action;
+mod archive;
 mod condition;
+mod conflict;
+mod content;
+mod context;
 mod engine;
+mod mime;
+mod pattern;
+mod plan;
+mod schedule;
+mod transfer;
+mod units;
 
-pub use action::Action;
-pub use condition::Condition;
-pub use engine::RuleEngine;
+pub use action::{Action, ActionOutput};
+pub use archive::ArchiveFormat;
+pub use condition::{AgeTimestamp, Condition};
+pub use conflict::ConflictStrategy;
+pub(crate) use conflict::same_content;
+pub use context::MatchContext;
+pub use engine::{AppliedRule, ProcessReport, RuleEngine, RuleFailure};
+pub use mime::FileKind;
+pub use plan::{ConflictOutcome, FilePlan, PlannedConflict, PlannedFailure, PlannedStep};
+pub use schedule::Schedule;
+pub use transfer::move_path;
+pub use units::{Age, ByteSize};
 
 use serde::{Deserialize, Serialize};
 
 /// A rule that matches files and performs actions
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "RuleDef", into = "RuleDef")]
 pub struct Rule {
     /// Human-readable name
     pub name: String,
 
     /// Whether the rule is active
-    #[serde(default = "default_enabled")]
     pub enabled: bool,
 
     /// Conditions to match (all must match)
-    #[serde(default)]
     pub condition: Condition,
 
-    /// Action to perform on matched files
-    pub action: Action,
+    /// Actions to perform on matched files, in order
+    ///
+    /// Each step runs on the path produced by the previous one. Configs can use
+    /// either a single `action` table or an `actions` list.
+    pub actions: Vec<Action>,
 
     /// Stop processing further rules if this matches
-    #[serde(default)]
     pub stop_processing: bool,
+
+    /// Re-check existing files in the watched folders on this schedule
+    pub rescan: Option<Schedule>,
+}
+
+/// On-disk form of a rule, accepting `action` or `actions`
+#[derive(Serialize, Deserialize)]
+struct RuleDef {
+    name: String,
+
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+
+    #[serde(default)]
+    condition: Condition,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    action: Option<Action>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    actions: Vec<Action>,
+
+    #[serde(default)]
+    stop_processing: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rescan: Option<Schedule>,
+}
+
+impl TryFrom<RuleDef> for Rule {
+    type Error = String;
+
+    fn try_from(def: RuleDef) -> Result<Self, Self::Error> {
+        let actions = match (def.action, def.actions) {
+            (Some(_), actions) if !actions.is_empty() => {
+                return Err(format!(
+                    "rule '{}' has both `action` and `actions`, use one of them",
+                    def.name
+                ));
+            }
+            (Some(action), _) => vec![action],
+            (None, actions) if !actions.is_empty() => actions,
+            (None, _) => {
+                return Err(format!(
+                    "rule '{}' needs an `action` or a non-empty `actions` list",
+                    def.name
+                ));
+            }
+        };
+
+        Ok(Self {
+            name: def.name,
+            enabled: def.enabled,
+            condition: def.condition,
+            actions,
+            stop_processing: def.stop_processing,
+            rescan: def.rescan,
+        })
+    }
+}
+
+impl From<Rule> for RuleDef {
+    fn from(rule: Rule) -> Self {
+        // Single-step rules keep the familiar `[rule.action]` form
+        let (action, actions) = if rule.actions.len() == 1 {
+            (rule.actions.into_iter().next(), Vec::new())
+        } else {
+            (None, rule.actions)
+        };
+
+        Self {
+            name: rule.name,
+            enabled: rule.enabled,
+            condition: rule.condition,
+            action,
+            actions,
+            stop_processing: rule.stop_processing,
+            rescan: rule.rescan,
+        }
+    }
 }
 
 fn default_enabled() -> bool {
@@ -37,14 +137,15 @@ fn default_enabled() -> bool {
 }
 
 impl Rule {
-    /// Create a new rule
+    /// Create a new rule with a single action
     pub fn new(name: impl Into<String>, condition: Condition, action: Action) -> Self {
         Self {
             name: name.into(),
             enabled: true,
             condition,
-            action,
+            actions: vec![action],
             stop_processing: false,
+            rescan: None,
         }
     }
 }
@@ -0,0 +1,550 @@
+//! Pattern expansion for rename patterns, destinations and commands
+//!
+//! A pattern is plain text with `{variable}` placeholders. Variables may take
+//! an argument (`{modified:%Y}`, `{hash:8}`) and a chain of filters
+//! (`{name|lower|replace: :_}`). Unknown placeholders are left untouched, so
+//! shell snippets like `awk '{print $1}'` survive expansion.
+//!
+//! Captures from file content (`content_regex`) are checked before they are
+//! used: in paths they must not contain path separators, `..` or NUL, and in
+//! shell commands they may only contain letters, digits, spaces and `._-+,@%:=`.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use super::MatchContext;
+use super::condition::added_time;
+use super::mime::FileKind;
+
+/// Upper bound for `{counter}` before giving up on finding a free path
+const MAX_COUNTER: u32 = 10_000;
+
+/// Where an expanded pattern ends up, which decides how captures from file
+/// content are checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expansion {
+    /// A path, or part of one
+    Path,
+    /// A command line run by the shell
+    Shell,
+    /// A single argument of a command run directly
+    Argument,
+}
+
+/// Expand pattern variables like {name}, {ext}, {modified:%Y}, {re:1} in a path
+pub fn expand_pattern(pattern: &str, path: &Path, ctx: &MatchContext) -> Result<String> {
+    expand_pattern_with_counter(pattern, path, ctx, 1)
+}
+
+/// Expand a pattern for a command line or a command argument
+pub fn expand_command(
+    pattern: &str,
+    path: &Path,
+    ctx: &MatchContext,
+    expansion: Expansion,
+) -> Result<String> {
+    expand(pattern, path, ctx, 1, expansion)
+}
+
+/// Expand a path pattern with a specific value for `{counter}`
+pub fn expand_pattern_with_counter(
+    pattern: &str,
+    path: &Path,
+    ctx: &MatchContext,
+    counter: u32,
+) -> Result<String> {
+    expand(pattern, path, ctx, counter, Expansion::Path)
+}
+
+fn expand(
+    pattern: &str,
+    path: &Path,
+    ctx: &MatchContext,
+    counter: u32,
+    expansion: Expansion,
+) -> Result<String> {
+    let mut result = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('}') else {
+            result.push_str(&rest[start..]);
+            return Ok(result);
+        };
+
+        let token = &after[..end];
+        match expand_token(token, path, ctx, counter, expansion)? {
+            Some(value) => result.push_str(&value),
+            None => {
+                result.push('{');
+                result.push_str(token);
+                result.push('}');
+            }
+        }
+        rest = &after[end + 1..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Expand a pattern into a path that doesn't exist yet
+///
+/// `to_path` turns the expanded text into the path to check. Without
+/// `{counter}` the pattern is expanded once; with it, the counter goes up from 1
+/// until the path is free (or is the file itself).
+pub fn expand_unique(
+    pattern: &str,
+    path: &Path,
+    ctx: &MatchContext,
+    to_path: impl Fn(String) -> PathBuf,
+) -> Result<PathBuf> {
+    if !uses_counter(pattern) {
+        return Ok(to_path(expand_pattern(pattern, path, ctx)?));
+    }
+
+    for counter in 1..MAX_COUNTER {
+        let candidate = to_path(expand_pattern_with_counter(pattern, path, ctx, counter)?);
+        if candidate == path || candidate.symlink_metadata().is_err() {
+            return Ok(candidate);
+        }
+    }
+
+    anyhow::bail!("No free path found for pattern {}", pattern)
+}
+
+/// Whether a pattern uses `{counter}`
+pub fn uses_counter(pattern: &str) -> bool {
+    pattern.split('{').skip(1).any(|part| {
+        let token = part.split('}').next().unwrap_or("");
+        let variable = token.split('|').next().unwrap_or("");
+        variable.trim() == "counter"
+    })
+}
+
+/// Expand one `{...}` token, or `None` if it isn't a known variable
+fn expand_token(
+    token: &str,
+    path: &Path,
+    ctx: &MatchContext,
+    counter: u32,
+    expansion: Expansion,
+) -> Result<Option<String>> {
+    let mut parts = token.split('|');
+    let variable = parts.next().unwrap_or("");
+    let (name, arg) = match variable.split_once(':') {
+        Some((name, arg)) => (name, Some(arg)),
+        None => (variable, None),
+    };
+
+    let Some(mut value) = variable_value(name, arg, path, ctx, counter)? else {
+        return Ok(None);
+    };
+
+    for filter in parts {
+        value = apply_filter(&value, filter)?;
+    }
+
+    // Filters run first, so `{re:customer|slug}` can make a value safe
+    if name == "re"
+        && let Some(group) = arg
+        && ctx.content_captures.contains(group)
+    {
+        check_content_capture(group, &value, expansion)?;
+    }
+
+    Ok(Some(value))
+}
+
+/// Refuse a capture from file content that could escape where it is put
+fn check_content_capture(group: &str, value: &str, expansion: Expansion) -> Result<()> {
+    let unsafe_for = match expansion {
+        Expansion::Path => value.contains(['/', '\\', '\0']) || value.contains(".."),
+        Expansion::Shell => !value
+            .chars()
+            .all(|c| c.is_alphanumeric() || " ._-+,@%:=".contains(c)),
+        Expansion::Argument => value.contains('\0'),
+    };
+    if unsafe_for {
+        anyhow::bail!(
+            "Capture '{}' from file content can't be used here: {:?}",
+            group,
+            value
+        );
+    }
+    Ok(())
+}
+
+/// Value of a variable, or `None` for unknown variables
+fn variable_value(
+    name: &str,
+    arg: Option<&str>,
+    path: &Path,
+    ctx: &MatchContext,
+    counter: u32,
+) -> Result<Option<String>> {
+    let lossy = |s: Option<&std::ffi::OsStr>| {
+        s.map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    let data = ctx.data_path(path);
+
+    let value = match name {
+        "path" => path.to_string_lossy().to_string(),
+        "dir" => path
+            .parent()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        "parent" => lossy(path.parent().and_then(|p| p.file_name())),
+        "name" => lossy(path.file_stem()),
+        "filename" => lossy(path.file_name()),
+        "relpath" => ctx.relative_path(path).to_string_lossy().to_string(),
+        "reldir" => lossy(ctx.relative_path(path).parent().map(|p| p.as_os_str())),
+        "ext" => lossy(path.extension()),
+        "date" => format_time(Local::now(), arg.unwrap_or("%Y-%m-%d"))?,
+        "datetime" => format_time(Local::now(), arg.unwrap_or("%Y-%m-%d_%H-%M-%S"))?,
+        "modified" => {
+            let modified = metadata(data)?.modified()?;
+            format_time(modified.into(), arg.unwrap_or("%Y-%m-%d"))?
+        }
+        "created" => {
+            // Not every filesystem records creation time, fall back to mtime
+            let meta = metadata(data)?;
+            let created = meta.created().or_else(|_| meta.modified())?;
+            format_time(created.into(), arg.unwrap_or("%Y-%m-%d"))?
+        }
+        "added" => {
+            let added = added_time(path, ctx)
+                .with_context(|| format!("Failed to read added date of {}", path.display()))?;
+            format_time(added.into(), arg.unwrap_or("%Y-%m-%d"))?
+        }
+        "size" => metadata(data)?.len().to_string(),
+        "size_human" => format_size(metadata(data)?.len()),
+        "counter" => counter.to_string(),
+        "kind" => FileKind::detect(data)
+            .map(|kind| kind.name())
+            .unwrap_or("other")
+            .to_string(),
+        "re" => {
+            let group = arg.context("{re:...} needs a capture group name or number")?;
+            ctx.captures
+                .get(group)
+                .with_context(|| format!("No regex capture group '{}' in this match", group))?
+                .clone()
+        }
+        "hash" => {
+            let hash = sha256_hex(data)?;
+            match arg {
+                Some(len) => {
+                    let len: usize = len
+                        .parse()
+                        .with_context(|| format!("Invalid hash length: {}", len))?;
+                    hash[..len.min(hash.len())].to_string()
+                }
+                None => hash,
+            }
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(value))
+}
+
+/// Apply a filter like `lower` or `replace:a:b` to a value
+fn apply_filter(value: &str, filter: &str) -> Result<String> {
+    let mut args = filter.split(':');
+    let name = args.next().unwrap_or("").trim();
+
+    let result = match name {
+        "lower" => value.to_lowercase(),
+        "upper" => value.to_uppercase(),
+        "trim" => value.trim().to_string(),
+        "slug" => slugify(value),
+        "replace" => {
+            let from = args
+                .next()
+                .context("replace filter needs replace:FROM:TO")?;
+            let to = args.next().unwrap_or("");
+            if from.is_empty() {
+                value.to_string()
+            } else {
+                value.replace(from, to)
+            }
+        }
+        "truncate" => {
+            let len: usize = args
+                .next()
+                .context("truncate filter needs truncate:LENGTH")?
+                .parse()
+                .context("Invalid truncate length")?;
+            value.chars().take(len).collect()
+        }
+        _ => anyhow::bail!("Unknown pattern filter: {}", name),
+    };
+
+    Ok(result)
+}
+
+fn metadata(path: &Path) -> Result<std::fs::Metadata> {
+    std::fs::metadata(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Format a time, rejecting invalid format strings instead of panicking
+fn format_time(time: DateTime<Local>, format: &str) -> Result<String> {
+    let mut out = String::new();
+    write!(out, "{}", time.format(format))
+        .map_err(|_| anyhow::anyhow!("Invalid date format: {}", format))?;
+    Ok(out)
+}
+
+/// Human-readable size, e.g. "1.5 MB"
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Lowercase words joined by dashes, e.g. "My Photo (1)" -> "my-photo-1"
+fn slugify(value: &str) -> String {
+    let mut slug = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// SHA-256 of a file's content as lowercase hex
+fn sha256_hex(path: &Path) -> Result<String> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    let mut hex = String::with_capacity(64);
+    for byte in hasher.finalize() {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    Ok(hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> MatchContext {
+        MatchContext::default()
+    }
+
+    #[test]
+    fn test_expand_pattern() {
+        let path = Path::new("/tmp/test.pdf");
+
+        assert_eq!(expand_pattern("{name}", path, &ctx()).unwrap(), "test");
+        assert_eq!(expand_pattern("{ext}", path, &ctx()).unwrap(), "pdf");
+        assert_eq!(
+            expand_pattern("{filename}", path, &ctx()).unwrap(),
+            "test.pdf"
+        );
+        assert_eq!(
+            expand_pattern("{name}.{ext}", path, &ctx()).unwrap(),
+            "test.pdf"
+        );
+        assert_eq!(expand_pattern("{parent}", path, &ctx()).unwrap(), "tmp");
+    }
+
+    #[test]
+    fn test_expand_metadata_variables() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("Holiday Photo.JPG");
+        std::fs::write(&path, "hello").unwrap();
+
+        let mtime = chrono::Local::now() - chrono::Duration::days(400);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(mtime.into())
+            .unwrap();
+
+        assert_eq!(
+            expand_pattern("{modified:%Y}/{modified:%m}", &path, &ctx()).unwrap(),
+            mtime.format("%Y/%m").to_string()
+        );
+        assert_eq!(expand_pattern("{size}", &path, &ctx()).unwrap(), "5");
+        assert_eq!(expand_pattern("{kind}", &path, &ctx()).unwrap(), "image");
+        assert_eq!(
+            expand_pattern("{size_human}", &path, &ctx()).unwrap(),
+            "5 B"
+        );
+        // sha256("hello")
+        assert_eq!(
+            expand_pattern("{hash:8}", &path, &ctx()).unwrap(),
+            "2cf24dba"
+        );
+        assert_eq!(
+            expand_pattern_with_counter("{name}_{counter}", &path, &ctx(), 3).unwrap(),
+            "Holiday Photo_3"
+        );
+        assert!(expand_pattern("{modified:%Q}", &path, &ctx()).is_err());
+    }
+
+    #[test]
+    fn test_expand_filters() {
+        let path = Path::new("/tmp/My Report (Final).PDF");
+
+        assert_eq!(
+            expand_pattern("{name|lower}.{ext|lower}", path, &ctx()).unwrap(),
+            "my report (final).pdf"
+        );
+        assert_eq!(
+            expand_pattern("{name|slug}", path, &ctx()).unwrap(),
+            "my-report-final"
+        );
+        assert_eq!(
+            expand_pattern("{name|replace: :_|upper}", path, &ctx()).unwrap(),
+            "MY_REPORT_(FINAL)"
+        );
+        assert_eq!(
+            expand_pattern("{name|truncate:2}", path, &ctx()).unwrap(),
+            "My"
+        );
+        assert!(expand_pattern("{name|nope}", path, &ctx()).is_err());
+    }
+
+    #[test]
+    fn test_unknown_placeholders_are_kept() {
+        let path = Path::new("/tmp/a.txt");
+        assert_eq!(
+            expand_pattern("awk '{print $1}' {filename}", path, &ctx()).unwrap(),
+            "awk '{print $1}' a.txt"
+        );
+        assert_eq!(
+            expand_pattern("{unclosed", path, &ctx()).unwrap(),
+            "{unclosed"
+        );
+        assert!(uses_counter("{name} ({counter})"));
+        assert!(!uses_counter("{name}"));
+    }
+
+    #[test]
+    fn test_expand_unique_counter() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("scan.png");
+        std::fs::write(&path, "").unwrap();
+        std::fs::write(temp.path().join("scan_1.png"), "").unwrap();
+
+        let to_path = |name: String| temp.path().join(name);
+        assert_eq!(
+            expand_unique("{name}_{counter}.{ext}", &path, &ctx(), to_path).unwrap(),
+            temp.path().join("scan_2.png")
+        );
+        assert_eq!(
+            expand_unique("{name}.{ext}", &path, &ctx(), to_path).unwrap(),
+            path
+        );
+    }
+
+    #[test]
+    fn test_expand_regex_captures() {
+        let path = Path::new("/tmp/INV-acme-0042.pdf");
+        let mut ctx = MatchContext::default();
+        ctx.captures
+            .insert("vendor".to_string(), "acme".to_string());
+        ctx.captures.insert("1".to_string(), "acme".to_string());
+        ctx.captures.insert("no".to_string(), "0042".to_string());
+
+        assert_eq!(
+            expand_pattern("~/Invoices/{re:vendor|upper}/{re:no}.{ext}", path, &ctx).unwrap(),
+            "~/Invoices/ACME/0042.pdf"
+        );
+        assert_eq!(expand_pattern("{re:1}", path, &ctx).unwrap(), "acme");
+        assert!(expand_pattern("{re:missing}", path, &ctx).is_err());
+    }
+
+    #[test]
+    fn test_content_captures_checked() {
+        let path = Path::new("/tmp/invoice.txt");
+        let mut ctx = MatchContext::default();
+        for (name, value) in [("id", "C-1042"), ("evil", "x; rm -rf ~"), ("up", "..")] {
+            ctx.captures.insert(name.to_string(), value.to_string());
+            ctx.content_captures.insert(name.to_string());
+        }
+
+        assert_eq!(expand_pattern("{re:id}", path, &ctx).unwrap(), "C-1042");
+        assert!(expand_pattern("/docs/{re:up}/x", path, &ctx).is_err());
+        let shell = |p| expand_command(p, path, &ctx, Expansion::Shell);
+        assert_eq!(shell("echo {re:id} | wc").unwrap(), "echo C-1042 | wc");
+        assert!(shell("echo {re:evil} | wc").is_err());
+        assert_eq!(
+            expand_command("{re:evil}", path, &ctx, Expansion::Argument).unwrap(),
+            "x; rm -rf ~"
+        );
+
+        // Captures from file names aren't restricted
+        ctx.content_captures.clear();
+        assert_eq!(expand_pattern("{re:up}", path, &ctx).unwrap(), "..");
+    }
+
+    #[test]
+    fn test_expand_relative_path() {
+        let ctx = MatchContext::with_root(Some(Path::new("/data/inbox")));
+        let path = Path::new("/data/inbox/exports/2024/a.csv");
+        assert_eq!(
+            expand_pattern("{relpath}", path, &ctx).unwrap(),
+            "exports/2024/a.csv"
+        );
+        assert_eq!(
+            expand_pattern("/backup/{reldir}", path, &ctx).unwrap(),
+            "/backup/exports/2024"
+        );
+
+        // Top-level files have an empty reldir
+        let top = Path::new("/data/inbox/a.csv");
+        assert_eq!(expand_pattern("{relpath}", top, &ctx).unwrap(), "a.csv");
+        assert_eq!(expand_pattern("{reldir}", top, &ctx).unwrap(), "");
+    }
+
+    #[test]
+    fn test_expand_added() {
+        let added = chrono::Local::now() - chrono::Duration::days(30);
+        let ctx = MatchContext::default().with_added(Some(added.into()));
+        assert_eq!(
+            expand_pattern("{added:%Y-%m}", Path::new("/tmp/a.zip"), &ctx).unwrap(),
+            added.format("%Y-%m").to_string()
+        );
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024), "5.0 GB");
+    }
+}
@@ -0,0 +1,232 @@
+//! Dry-run plans - what processing a file would do, without doing it
+
+use serde::Serialize;
+use std::fmt;
+use std::path::PathBuf;
+
+use super::ActionOutput;
+
+/// What processing a file would do
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FilePlan {
+    /// The file as it is now
+    pub path: PathBuf,
+
+    /// Steps that would run, in order
+    pub steps: Vec<PlannedStep>,
+
+    /// Step that would fail, if any (processing would stop there)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failure: Option<PlannedFailure>,
+}
+
+impl FilePlan {
+    /// Whether nothing would happen to the file
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty() && self.failure.is_none()
+    }
+
+    /// Names of the rules that would run (or fail), in order
+    pub fn rules(&self) -> Vec<&str> {
+        let mut rules: Vec<&str> = Vec::new();
+        let steps = self.steps.iter().map(|s| s.rule.as_str());
+        for rule in steps.chain(self.failure.iter().map(|f| f.rule.as_str())) {
+            if !rules.contains(&rule) {
+                rules.push(rule);
+            }
+        }
+        rules
+    }
+}
+
+/// A step that would fail
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedFailure {
+    /// Name of the rule
+    pub rule: String,
+
+    /// Index of the failing action in the rule's pipeline
+    pub step: usize,
+
+    /// Config name of the action type (None if checking the conditions
+    /// would fail)
+    pub action: Option<&'static str>,
+
+    /// Why it would fail
+    pub error: String,
+}
+
+impl fmt::Display for PlannedFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.action {
+            Some(action) => write!(
+                f,
+                "Rule '{}' would fail at step {} ({}): {}",
+                self.rule,
+                self.step + 1,
+                action,
+                self.error
+            ),
+            None => write!(
+                f,
+                "Rule '{}' would fail checking conditions: {}",
+                self.rule, self.error
+            ),
+        }
+    }
+}
+
+/// One action a plan would run
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedStep {
+    /// Name of the rule
+    pub rule: String,
+
+    /// Index of the action in the rule's pipeline
+    pub step: usize,
+
+    /// Config name of the action type
+    pub action: &'static str,
+
+    /// Path the action would run on
+    pub source: PathBuf,
+
+    /// Where the file would be afterwards (None if it would be gone)
+    pub result: Option<PathBuf>,
+
+    /// File or folder the action would make besides it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created: Option<PathBuf>,
+
+    /// Command line a `run` action would execute
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+
+    /// Existing file in the way, and what would happen about it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<PlannedConflict>,
+
+    /// Things worth knowing before running it for real
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+impl PlannedStep {
+    /// Short description of the outcome: the new path, what gets made, or the
+    /// command that runs
+    pub fn target(&self) -> String {
+        if let Some(ref command) = self.command {
+            return format!("$ {}", command);
+        }
+        if let Some(ref created) = self.created {
+            return created.display().to_string();
+        }
+        match self.result {
+            Some(ref result) if *result == self.source => "(unchanged)".to_string(),
+            Some(ref result) => result.display().to_string(),
+            None => match self.action {
+                "trash" => "(trash)".to_string(),
+                "delete" => "(deleted)".to_string(),
+                _ => "(removed)".to_string(),
+            },
+        }
+    }
+
+    /// The conflict and warnings on one line
+    pub fn notes(&self) -> String {
+        self.conflict
+            .iter()
+            .map(ToString::to_string)
+            .chain(self.warnings.iter().cloned())
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// An existing file at a step's target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedConflict {
+    /// The file already there
+    pub existing: PathBuf,
+
+    /// What the conflict strategy would do
+    pub outcome: ConflictOutcome,
+}
+
+impl fmt::Display for PlannedConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.outcome)
+    }
+}
+
+/// How a conflict would be resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictOutcome {
+    /// The file gets a free name next to the existing one
+    Rename,
+    /// The existing file is replaced
+    Overwrite,
+    /// The file stays where it is
+    Skip,
+    /// The existing file is identical, the incoming one is removed
+    Duplicate,
+    /// The step fails (`conflict = "error"`)
+    Error,
+}
+
+impl fmt::Display for ConflictOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConflictOutcome::Rename => "target exists, renamed",
+            ConflictOutcome::Overwrite => "target exists, overwritten",
+            ConflictOutcome::Skip => "target exists, skipped",
+            ConflictOutcome::Duplicate => "identical file exists, duplicate removed",
+            ConflictOutcome::Error => "target exists, step fails",
+        })
+    }
+}
+
+/// What an action would do, before the engine says which rule and step it is
+#[derive(Debug, Default)]
+pub(crate) struct Prediction {
+    pub output: ActionOutput,
+    pub command: Option<String>,
+    pub conflict: Option<PlannedConflict>,
+    pub warnings: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_target_and_notes() {
+        let mut step = PlannedStep {
+            rule: "PDFs".to_string(),
+            step: 0,
+            action: "move",
+            source: PathBuf::from("/in/a.pdf"),
+            result: Some(PathBuf::from("/docs/a (2).pdf")),
+            created: None,
+            command: None,
+            conflict: Some(PlannedConflict {
+                existing: PathBuf::from("/docs/a.pdf"),
+                outcome: ConflictOutcome::Rename,
+            }),
+            warnings: vec!["crosses filesystems".to_string()],
+        };
+        assert_eq!(step.target(), "/docs/a (2).pdf");
+        assert_eq!(step.notes(), "target exists, renamed; crosses filesystems");
+
+        step.action = "trash";
+        step.result = None;
+        step.conflict = None;
+        assert_eq!(step.target(), "(trash)");
+        assert_eq!(step.notes(), "crosses filesystems");
+
+        let json = serde_json::to_value(&step).unwrap();
+        assert_eq!(json["action"], "trash");
+        assert!(json.get("conflict").is_none());
+    }
+}
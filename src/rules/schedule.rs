@@ -0,0 +1,119 @@
+//! Rescan schedules - an interval ("6h") or a cron expression ("0 3 * * *")
+
+use chrono::{DateTime, Local};
+use croner::Cron;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::str::FromStr;
+
+use super::Age;
+
+/// When to re-scan a watched folder
+///
+/// Written as an interval with an age unit ("30m", "6h", "1d") or as a cron
+/// expression with five or six fields ("0 3 * * *" runs daily at 03:00 local
+/// time).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schedule {
+    /// Every fixed interval
+    Every(Age),
+    /// At the times matching a cron expression
+    Cron(Box<Cron>),
+}
+
+impl Schedule {
+    /// The first scan time strictly after `time`
+    pub fn next_after(&self, time: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            Schedule::Every(age) => chrono::Duration::from_std(age.duration())
+                .ok()
+                .and_then(|interval| time.checked_add_signed(interval)),
+            Schedule::Cron(cron) => cron.find_next_occurrence(&time, false).ok(),
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        // Cron expressions always have several fields, intervals never do
+        if s.contains(char::is_whitespace) {
+            return Cron::from_str(s)
+                .map(|cron| Schedule::Cron(Box::new(cron)))
+                .map_err(|e| format!("invalid cron expression {:?}: {}", s, e));
+        }
+
+        let age: Age = s.parse()?;
+        if age.duration().is_zero() {
+            return Err(format!("rescan interval must be positive: {:?}", s));
+        }
+        Ok(Schedule::Every(age))
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Schedule::Every(age) => write!(f, "{}", age),
+            Schedule::Cron(cron) => write!(f, "{}", cron.as_str()),
+        }
+    }
+}
+
+impl Serialize for Schedule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Timelike};
+
+    #[test]
+    fn test_parse_schedule() {
+        assert_eq!(
+            "6h".parse::<Schedule>().unwrap(),
+            Schedule::Every("6h".parse().unwrap())
+        );
+        assert!(matches!(
+            "0 3 * * *".parse::<Schedule>().unwrap(),
+            Schedule::Cron(_)
+        ));
+        assert!("0s".parse::<Schedule>().is_err());
+        assert!("61 * * * *".parse::<Schedule>().is_err());
+        assert!("soon".parse::<Schedule>().is_err());
+
+        assert_eq!(
+            "0 3 * * *".parse::<Schedule>().unwrap().to_string(),
+            "0 3 * * *"
+        );
+        assert_eq!("90m".parse::<Schedule>().unwrap().to_string(), "90m");
+    }
+
+    #[test]
+    fn test_next_after() {
+        let now = Local.with_ymd_and_hms(2024, 5, 1, 10, 30, 0).unwrap();
+
+        let every = "90m".parse::<Schedule>().unwrap();
+        assert_eq!(
+            every.next_after(now).unwrap(),
+            Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+        );
+
+        let nightly = "0 3 * * *".parse::<Schedule>().unwrap();
+        let next = nightly.next_after(now).unwrap();
+        assert_eq!(next.date_naive().to_string(), "2024-05-02");
+        assert_eq!((next.hour(), next.minute()), (3, 0));
+    }
+}
@@ -0,0 +1,269 @@
+//! Moving files and directories, including across filesystems
+
+use anyhow::{Context, Result};
+use std::fs::{self, File};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+/// Move `source` to `dest`, falling back to copy-verify-delete across filesystems
+///
+/// `dest` is replaced if it is an existing file, like `std::fs::rename`.
+pub fn move_path(source: &Path, dest: &Path) -> Result<()> {
+    match fs::rename(source, dest) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            info!(
+                "{} is on another filesystem, copying instead",
+                dest.display()
+            );
+            copy_then_remove(source, dest)
+        }
+        Err(e) => Err(e)
+            .with_context(|| format!("Failed to move {} to {}", source.display(), dest.display())),
+    }
+}
+
+/// Whether moving `source` to `dest` would cross filesystems
+///
+/// Either path may not exist yet; the nearest existing folder above it decides.
+#[cfg(unix)]
+pub fn crosses_filesystems(source: &Path, dest: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    let device = |path: &Path| {
+        path.ancestors()
+            .find_map(|p| p.metadata().ok())
+            .map(|m| m.dev())
+    };
+    match (device(source), device(dest)) {
+        (Some(a), Some(b)) => a != b,
+        _ => false,
+    }
+}
+
+/// Whether moving `source` to `dest` would cross filesystems (unknown here)
+#[cfg(not(unix))]
+pub fn crosses_filesystems(_source: &Path, _dest: &Path) -> bool {
+    false
+}
+
+/// Copy `source` next to `dest`, verify it, move it into place and remove `source`
+///
+/// The copy is staged under a hidden `.partial` name, so `dest` never holds a
+/// half-written file, and the staging copy is removed if anything fails.
+pub fn copy_then_remove(source: &Path, dest: &Path) -> Result<()> {
+    let parent = dest.parent().unwrap_or(Path::new("."));
+    let name = dest.file_name().context("Destination has no name")?;
+    let staging = parent.join(format!(".{}.partial", name.to_string_lossy()));
+
+    if staging.symlink_metadata().is_ok() {
+        remove_any(&staging)?;
+    }
+
+    let result = copy_recursive(source, &staging)
+        .and_then(|()| verify_copy(source, &staging))
+        .and_then(|()| {
+            fs::rename(&staging, dest)
+                .with_context(|| format!("Failed to move copy into {}", dest.display()))
+        });
+    if let Err(e) = result {
+        let _ = remove_any(&staging);
+        return Err(e)
+            .with_context(|| format!("Failed to copy {} to {}", source.display(), dest.display()));
+    }
+
+    debug!(
+        "Copied {} -> {}, removing source",
+        source.display(),
+        dest.display()
+    );
+    remove_any(source)
+        .with_context(|| format!("Copied to {} but failed to remove source", dest.display()))
+}
+
+/// Copy a file, directory or symlink, preserving permissions and mtimes
+fn copy_recursive(source: &Path, dest: &Path) -> Result<()> {
+    let meta = source
+        .symlink_metadata()
+        .with_context(|| format!("Failed to read {}", source.display()))?;
+
+    if meta.file_type().is_symlink() {
+        let target = fs::read_link(source)?;
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&target, dest)?;
+            return Ok(());
+        }
+        #[cfg(not(unix))]
+        anyhow::bail!(
+            "Cannot copy symlink {} -> {}",
+            source.display(),
+            target.display()
+        );
+    }
+
+    if meta.is_dir() {
+        fs::create_dir(dest).with_context(|| format!("Failed to create {}", dest.display()))?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &dest.join(entry.file_name()))?;
+        }
+        fs::set_permissions(dest, meta.permissions())?;
+    } else if meta.is_file() {
+        // fs::copy streams the data and carries the permission bits over
+        fs::copy(source, dest).with_context(|| format!("Failed to copy {}", source.display()))?;
+    } else {
+        anyhow::bail!("Cannot copy special file {}", source.display());
+    }
+
+    // Directories get their mtime last, after their contents were written.
+    // Setting it doesn't need write access, so read-only copies work too.
+    if let Ok(modified) = meta.modified() {
+        File::open(dest)?
+            .set_modified(modified)
+            .with_context(|| format!("Failed to set mtime on {}", dest.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Check that the copy has the same tree and file sizes as the source
+fn verify_copy(source: &Path, copy: &Path) -> Result<()> {
+    let expected = tree_summary(source)?;
+    let actual = tree_summary(copy)?;
+    if expected != actual {
+        anyhow::bail!(
+            "Copy of {} does not match the source ({} vs {} entries)",
+            source.display(),
+            expected.len(),
+            actual.len()
+        );
+    }
+    Ok(())
+}
+
+/// Relative path and size (files only) of every entry under `root`, sorted
+fn tree_summary(root: &Path) -> Result<Vec<(PathBuf, Option<u64>)>> {
+    fn walk(root: &Path, path: &Path, out: &mut Vec<(PathBuf, Option<u64>)>) -> Result<()> {
+        let meta = path.symlink_metadata()?;
+        let relative = path.strip_prefix(root)?.to_path_buf();
+        if meta.is_dir() {
+            out.push((relative, None));
+            for entry in fs::read_dir(path)? {
+                walk(root, &entry?.path(), out)?;
+            }
+        } else if meta.is_file() {
+            out.push((relative, Some(meta.len())));
+        } else {
+            out.push((relative, None));
+        }
+        Ok(())
+    }
+
+    let mut out = Vec::new();
+    walk(root, root, &mut out)?;
+    out.sort();
+    Ok(out)
+}
+
+/// Remove a file, symlink or directory tree
+fn remove_any(path: &Path) -> Result<()> {
+    let meta = path.symlink_metadata()?;
+    if meta.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_copy_then_remove_directory() {
+        let temp = tempfile::tempdir().unwrap();
+        let source = temp.path().join("project");
+        std::fs::create_dir_all(source.join("src")).unwrap();
+        std::fs::write(source.join("src").join("main.rs"), "fn main() {}").unwrap();
+        std::fs::write(source.join("README"), "readme").unwrap();
+
+        let old = SystemTime::now() - Duration::from_secs(86_400);
+        File::options()
+            .write(true)
+            .open(source.join("README"))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        let dest = temp.path().join("moved");
+        copy_then_remove(&source, &dest).unwrap();
+
+        assert!(!source.exists());
+        assert!(!temp.path().join(".moved.partial").exists());
+        assert_eq!(
+            std::fs::read_to_string(dest.join("src").join("main.rs")).unwrap(),
+            "fn main() {}"
+        );
+        let modified = std::fs::metadata(dest.join("README"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        assert_eq!(modified, old);
+    }
+
+    #[test]
+    fn test_copy_then_remove_cleans_up_on_failure() {
+        let temp = tempfile::tempdir().unwrap();
+        let source = temp.path().join("a.txt");
+        std::fs::write(&source, "data").unwrap();
+
+        // Destination folder missing: the staging copy can't be created
+        let dest = temp.path().join("missing").join("a.txt");
+        assert!(copy_then_remove(&source, &dest).is_err());
+        assert!(source.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_preserves_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempfile::tempdir().unwrap();
+        let source = temp.path().join("run.sh");
+        std::fs::write(&source, "#!/bin/sh").unwrap();
+        std::fs::set_permissions(&source, std::fs::Permissions::from_mode(0o750)).unwrap();
+
+        let dest = temp.path().join("bin.sh");
+        copy_then_remove(&source, &dest).unwrap();
+        let mode = std::fs::metadata(&dest).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o750);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_read_only_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempfile::tempdir().unwrap();
+        let source = temp.path().join("signed.pdf");
+        std::fs::write(&source, "pdf").unwrap();
+        let old = SystemTime::now() - Duration::from_secs(86_400);
+        File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        std::fs::set_permissions(&source, std::fs::Permissions::from_mode(0o444)).unwrap();
+
+        let dest = temp.path().join("archive.pdf");
+        copy_then_remove(&source, &dest).unwrap();
+        let meta = std::fs::metadata(&dest).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o777, 0o444);
+        assert_eq!(meta.modified().unwrap(), old);
+    }
+}
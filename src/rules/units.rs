@@ -0,0 +1,272 @@
+//! Human-readable sizes ("25MB", "1.5GiB") and durations ("90m", "2w")
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Size units, largest first; SI units are powers of 1000, IEC units of 1024
+const SIZE_UNITS: &[(&str, u64)] = &[
+    ("TiB", 1 << 40),
+    ("TB", 1_000_000_000_000),
+    ("GiB", 1 << 30),
+    ("GB", 1_000_000_000),
+    ("MiB", 1 << 20),
+    ("MB", 1_000_000),
+    ("KiB", 1 << 10),
+    ("KB", 1_000),
+];
+
+/// Duration units, largest first
+const DURATION_UNITS: &[(&str, u64)] = &[
+    ("w", 7 * 86_400),
+    ("d", 86_400),
+    ("h", 3_600),
+    ("m", 60),
+    ("s", 1),
+];
+
+/// A size in bytes, written as a number of bytes or with a unit like "25MB"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    /// Number of bytes
+    pub fn bytes(self) -> u64 {
+        self.0
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (value, unit) = split_number(s).ok_or_else(|| format!("invalid size: {:?}", s))?;
+        let multiplier = match unit {
+            "" | "b" | "B" => 1,
+            unit => SIZE_UNITS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+                .map(|(_, multiplier)| *multiplier)
+                .ok_or_else(|| {
+                    format!(
+                        "unknown size unit {:?} in {:?} (use B, KB, MB, GB, TB, KiB, MiB, GiB or TiB)",
+                        unit, s
+                    )
+                })?,
+        };
+        scale(value, multiplier)
+            .map(Self)
+            .ok_or_else(|| format!("size too large: {:?}", s))
+    }
+}
+
+impl std::fmt::Display for ByteSize {
+    /// Shortest exact form, e.g. "25MB", "1536MiB", or plain bytes
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match exact_unit(self.0, SIZE_UNITS) {
+            Some((value, unit)) => write!(f, "{}{}", value, unit),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl Serialize for ByteSize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match exact_unit(self.0, SIZE_UNITS) {
+            Some(_) => serializer.collect_str(self),
+            None => serializer.serialize_u64(self.0),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match NumberOrString::deserialize(deserializer)? {
+            NumberOrString::Number(bytes) => Ok(Self(bytes)),
+            NumberOrString::String(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// A file age, written as a number of days or with a unit like "90m", "36h" or "2w"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Age(pub Duration);
+
+impl Age {
+    /// Age of a whole number of days (saturating at the longest duration)
+    pub fn days(days: u64) -> Self {
+        Self(Duration::from_secs(days.saturating_mul(86_400)))
+    }
+
+    /// The age as a duration
+    pub fn duration(self) -> Duration {
+        self.0
+    }
+}
+
+impl FromStr for Age {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (value, unit) = split_number(s).ok_or_else(|| format!("invalid age: {:?}", s))?;
+        let seconds = match unit.to_ascii_lowercase().as_str() {
+            // Plain numbers are days, like the original `age_days_*` fields
+            "" | "d" | "day" | "days" => 86_400,
+            "w" | "week" | "weeks" => 7 * 86_400,
+            "h" | "hour" | "hours" => 3_600,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60,
+            "s" | "sec" | "secs" | "second" | "seconds" => 1,
+            unit => {
+                return Err(format!(
+                    "unknown age unit {:?} in {:?} (use s, m, h, d or w)",
+                    unit, s
+                ));
+            }
+        };
+        scale(value, seconds)
+            .map(|secs| Self(Duration::from_secs(secs)))
+            .ok_or_else(|| format!("age too large: {:?}", s))
+    }
+}
+
+impl std::fmt::Display for Age {
+    /// Largest exact unit, e.g. "2w", "36h", "90m"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match exact_unit(self.0.as_secs(), DURATION_UNITS) {
+            Some((value, unit)) => write!(f, "{}{}", value, unit),
+            None => write!(f, "0d"),
+        }
+    }
+}
+
+impl Serialize for Age {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Whole days stay plain numbers so older versions can read them
+        let secs = self.0.as_secs();
+        if secs.is_multiple_of(86_400) {
+            serializer.serialize_u64(secs / 86_400)
+        } else {
+            serializer.collect_str(self)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Age {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match NumberOrString::deserialize(deserializer)? {
+            NumberOrString::Number(days) => days
+                .checked_mul(86_400)
+                .map(|secs| Self(Duration::from_secs(secs)))
+                .ok_or_else(|| serde::de::Error::custom(format!("age too large: {} days", days))),
+            NumberOrString::String(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Number(u64),
+    String(String),
+}
+
+/// Split "1.5 GiB" into (1.5, "GiB")
+fn split_number(s: &str) -> Option<(f64, &str)> {
+    let s = s.trim();
+    let end = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let value: f64 = s[..end].parse().ok()?;
+    Some((value, s[end..].trim()))
+}
+
+/// `value` units of `multiplier`, rounded, if that fits in a u64
+fn scale(value: f64, multiplier: u64) -> Option<u64> {
+    let scaled = (value * multiplier as f64).round();
+    // u64::MAX rounds up to 2^64 as a float, which no longer fits
+    (scaled.is_finite() && scaled < u64::MAX as f64).then_some(scaled as u64)
+}
+
+/// Express `value` in the unit giving the smallest whole number, if any unit fits
+fn exact_unit(value: u64, units: &[(&'static str, u64)]) -> Option<(u64, &'static str)> {
+    if value == 0 {
+        return None;
+    }
+    units
+        .iter()
+        .filter(|(_, multiplier)| value.is_multiple_of(*multiplier))
+        .map(|(name, multiplier)| (value / multiplier, *name))
+        .min_by_key(|(count, _)| *count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!("1048576".parse::<ByteSize>().unwrap().bytes(), 1_048_576);
+        assert_eq!("25MB".parse::<ByteSize>().unwrap().bytes(), 25_000_000);
+        assert_eq!("25 mb".parse::<ByteSize>().unwrap().bytes(), 25_000_000);
+        assert_eq!("1.5GiB".parse::<ByteSize>().unwrap().bytes(), 1_610_612_736);
+        assert_eq!("10KiB".parse::<ByteSize>().unwrap().bytes(), 10_240);
+        assert!("25XB".parse::<ByteSize>().is_err());
+        assert!("MB".parse::<ByteSize>().is_err());
+        assert!("20000000TiB".parse::<ByteSize>().is_err());
+        assert!(
+            format!("{}TB", "9".repeat(400))
+                .parse::<ByteSize>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_display_byte_size() {
+        assert_eq!(ByteSize(25_000_000).to_string(), "25MB");
+        assert_eq!(ByteSize(1_610_612_736).to_string(), "1536MiB");
+        assert_eq!(ByteSize(1_048_576).to_string(), "1MiB");
+        assert_eq!(ByteSize(1234).to_string(), "1234");
+    }
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!("7".parse::<Age>().unwrap(), Age::days(7));
+        assert_eq!(
+            "90m".parse::<Age>().unwrap().duration(),
+            Duration::from_secs(5400)
+        );
+        assert_eq!(
+            "36h".parse::<Age>().unwrap().duration(),
+            Duration::from_secs(36 * 3600)
+        );
+        assert_eq!("2w".parse::<Age>().unwrap(), Age::days(14));
+        assert!("2y".parse::<Age>().is_err());
+        assert!("40000000000000w".parse::<Age>().is_err());
+        assert_eq!(Age::days(u64::MAX).duration().as_secs(), u64::MAX);
+
+        assert_eq!("36h".parse::<Age>().unwrap().to_string(), "36h");
+        assert_eq!(Age::days(14).to_string(), "2w");
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        #[derive(Serialize, Deserialize)]
+        struct Wrapper {
+            size: ByteSize,
+            age: Age,
+            days: Age,
+        }
+
+        let parsed: Wrapper = toml::from_str("size = \"25MB\"\nage = \"90m\"\ndays = 30").unwrap();
+        assert_eq!(parsed.size.bytes(), 25_000_000);
+        assert_eq!(parsed.days, Age::days(30));
+
+        let saved = toml::to_string(&parsed).unwrap();
+        assert!(saved.contains("size = \"25MB\""));
+        assert!(saved.contains("age = \"90m\""));
+        assert!(saved.contains("days = 30"));
+
+        let huge = format!("size = 1\nage = 1\ndays = {}", u64::MAX / 1000);
+        assert!(toml::from_str::<Wrapper>(&huge).is_err());
+    }
+}
@@ -0,0 +1,495 @@
+//! Trash support following the FreeDesktop.org Trash specification
+//!
+//! Files go to the home trash (`$XDG_DATA_HOME/Trash`) when they live on the
+//! same filesystem, otherwise to `$topdir/.Trash/$uid` or `$topdir/.Trash-$uid`
+//! on their own mount. If neither can be used (a read-only mount, say), they
+//! are copied to the home trash instead. Every trashed file gets a `.trashinfo`
+//! entry so file managers can restore it, and hazelnut keeps its own index of
+//! what it trashed for `hazelnut trash list/restore`. Writers to the index
+//! hold a lock on `trash.lock`.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::Config;
+
+/// A file hazelnut moved to the trash
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrashedItem {
+    /// Trash directory holding the file (contains `files/` and `info/`)
+    pub trash_dir: PathBuf,
+    /// Name of the file inside `files/`
+    pub name: String,
+    /// Absolute path the file was trashed from
+    pub original: PathBuf,
+    /// When the file was trashed
+    pub deleted_at: DateTime<Local>,
+}
+
+impl TrashedItem {
+    /// Location of the trashed file
+    pub fn file_path(&self) -> PathBuf {
+        self.trash_dir.join("files").join(&self.name)
+    }
+
+    /// Location of the `.trashinfo` file
+    pub fn info_path(&self) -> PathBuf {
+        self.trash_dir
+            .join("info")
+            .join(format!("{}.trashinfo", self.name))
+    }
+}
+
+/// Move a file or directory to the appropriate trash and record it
+pub fn trash(path: &Path) -> Result<TrashedItem> {
+    let original = absolute(path)?;
+    let (trash_dir, top_dir) = trash_dir_for(&original)?;
+    let item = move_to_trash(&original, &trash_dir, top_dir.as_deref())?;
+
+    if let Some(index) = index_path()
+        && let Err(e) = record(&index, &item)
+    {
+        warn!("Failed to record {} in trash index: {}", path.display(), e);
+    }
+
+    Ok(item)
+}
+
+/// Move a file into a specific trash directory
+///
+/// `top_dir` is the mount point for per-mount trash directories; the
+/// `.trashinfo` path is then stored relative to it, as the spec recommends.
+pub fn move_to_trash(path: &Path, trash_dir: &Path, top_dir: Option<&Path>) -> Result<TrashedItem> {
+    let files_dir = trash_dir.join("files");
+    let info_dir = trash_dir.join("info");
+    create_private_dir(&files_dir)?;
+    create_private_dir(&info_dir)?;
+
+    let file_name = path
+        .file_name()
+        .context("File has no name")?
+        .to_string_lossy()
+        .to_string();
+
+    let info_path_str = match top_dir.and_then(|top| path.strip_prefix(top).ok()) {
+        Some(relative) => encode_path(relative),
+        None => encode_path(path),
+    };
+    let deleted_at = Local::now();
+    let info = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        info_path_str,
+        deleted_at.format("%Y-%m-%dT%H:%M:%S")
+    );
+
+    // Reserve a unique name by atomically creating the .trashinfo file first
+    let mut counter = 1;
+    let (name, info_path) = loop {
+        let name = unique_name(&file_name, counter);
+        counter += 1;
+
+        let info_path = info_dir.join(format!("{}.trashinfo", name));
+        if files_dir.join(&name).symlink_metadata().is_ok() {
+            continue;
+        }
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(mut file) => {
+                file.write_all(info.as_bytes())
+                    .with_context(|| format!("Failed to write {}", info_path.display()))?;
+                break (name, info_path);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to create {}", info_path.display()));
+            }
+        }
+    };
+
+    let dest = files_dir.join(&name);
+    debug!("Trashing {} -> {}", path.display(), dest.display());
+    // Copies across filesystems when the mount's own trash couldn't be used
+    if let Err(e) = crate::rules::move_path(path, &dest) {
+        let _ = fs::remove_file(&info_path);
+        return Err(e).with_context(|| {
+            format!(
+                "Failed to move {} to trash {}",
+                path.display(),
+                trash_dir.display()
+            )
+        });
+    }
+
+    Ok(TrashedItem {
+        trash_dir: trash_dir.to_path_buf(),
+        name,
+        original: path.to_path_buf(),
+        deleted_at,
+    })
+}
+
+/// Put a trashed file back where it came from and drop it from the index
+pub fn restore(item: &TrashedItem) -> Result<()> {
+    put_back(item)?;
+    if let Some(index) = index_path() {
+        forget(&index, item)?;
+    }
+    Ok(())
+}
+
+/// Move a trashed file back to its original location and remove its `.trashinfo`
+pub fn put_back(item: &TrashedItem) -> Result<()> {
+    let source = item.file_path();
+    if source.symlink_metadata().is_err() {
+        anyhow::bail!("{} is no longer in the trash", item.original.display());
+    }
+    if item.original.symlink_metadata().is_ok() {
+        anyhow::bail!(
+            "Cannot restore, a file already exists at {}",
+            item.original.display()
+        );
+    }
+
+    if let Some(parent) = item.original.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    crate::rules::move_path(&source, &item.original).with_context(|| {
+        format!(
+            "Failed to restore {} to {}",
+            source.display(),
+            item.original.display()
+        )
+    })?;
+
+    if let Err(e) = fs::remove_file(item.info_path()) {
+        warn!("Failed to remove {}: {}", item.info_path().display(), e);
+    }
+
+    Ok(())
+}
+
+/// List files hazelnut trashed that are still in the trash, oldest first
+pub fn list() -> Result<Vec<TrashedItem>> {
+    match index_path() {
+        Some(index) => list_in(&index),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Path of hazelnut's trash index
+pub fn index_path() -> Option<PathBuf> {
+    Config::state_dir().map(|d| d.join("trash.jsonl"))
+}
+
+/// Read an index, skipping entries that were emptied from the trash
+pub fn list_in(index: &Path) -> Result<Vec<TrashedItem>> {
+    let content = match fs::read_to_string(index) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read {}", index.display()));
+        }
+    };
+
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<TrashedItem>(line) {
+            Ok(item) => Some(item),
+            Err(e) => {
+                warn!("Skipping invalid trash index entry: {}", e);
+                None
+            }
+        })
+        .filter(|item| item.file_path().symlink_metadata().is_ok())
+        .collect())
+}
+
+/// Append an item to an index
+pub fn record(index: &Path, item: &TrashedItem) -> Result<()> {
+    let _lock = lock(index)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(index)
+        .with_context(|| format!("Failed to open {}", index.display()))?;
+    writeln!(file, "{}", serde_json::to_string(item)?)?;
+    Ok(())
+}
+
+/// Remove an item from an index (also drops entries no longer in the trash)
+pub fn forget(index: &Path, item: &TrashedItem) -> Result<()> {
+    // Hold the lock across the rewrite so the daemon's appends aren't lost
+    let _lock = lock(index)?;
+    let remaining: Vec<_> = list_in(index)?.into_iter().filter(|i| i != item).collect();
+
+    let mut content = String::new();
+    for item in &remaining {
+        content.push_str(&serde_json::to_string(item)?);
+        content.push('\n');
+    }
+    let partial = index.with_extension("jsonl.partial");
+    fs::write(&partial, content)
+        .with_context(|| format!("Failed to write {}", partial.display()))?;
+    fs::rename(&partial, index).with_context(|| format!("Failed to write {}", index.display()))
+}
+
+/// Take the index's write lock, released when the file is dropped
+fn lock(index: &Path) -> Result<File> {
+    if let Some(parent) = index.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let path = index.with_extension("lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    file.lock()
+        .with_context(|| format!("Failed to lock {}", path.display()))?;
+    Ok(file)
+}
+
+/// The user's home trash directory
+pub fn home_trash_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|d| d.join("Trash"))
+}
+
+/// Pick the trash directory for a file, plus the mount point for per-mount trash
+#[cfg(unix)]
+fn trash_dir_for(path: &Path) -> Result<(PathBuf, Option<PathBuf>)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let home_trash = home_trash_dir().context("Could not determine home trash directory")?;
+    let file_dev = path
+        .symlink_metadata()
+        .with_context(|| format!("Failed to read {}", path.display()))?
+        .dev();
+
+    let home_dev = home_trash
+        .ancestors()
+        .find_map(|p| p.metadata().ok())
+        .map(|m| m.dev());
+    if home_dev == Some(file_dev) {
+        return Ok((home_trash, None));
+    }
+
+    // Walk up to the mount point: the last ancestor on the same device
+    let mut top_dir = path.parent().context("File has no parent")?;
+    while let Some(parent) = top_dir.parent() {
+        match parent.metadata() {
+            Ok(m) if m.dev() == file_dev => top_dir = parent,
+            _ => break,
+        }
+    }
+
+    let uid = unsafe { libc::getuid() };
+    match mount_trash_dir(top_dir, uid) {
+        Ok(dir) => Ok((dir, Some(top_dir.to_path_buf()))),
+        Err(e) => {
+            warn!("{:#}, using the home trash instead", e);
+            Ok((home_trash, None))
+        }
+    }
+}
+
+/// The trash directory on a mount: `.Trash/$uid` if the admin set it up,
+/// otherwise `.Trash-$uid`
+#[cfg(unix)]
+fn mount_trash_dir(top_dir: &Path, uid: u32) -> Result<PathBuf> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    // $topdir/.Trash must be a real directory with the sticky bit set
+    let shared = top_dir.join(".Trash");
+    if let Ok(meta) = shared.symlink_metadata()
+        && meta.is_dir()
+        && meta.permissions().mode() & 0o1000 != 0
+    {
+        let dir = shared.join(uid.to_string());
+        if create_private_dir(&dir).is_ok() {
+            return Ok(dir);
+        }
+    }
+
+    let dir = top_dir.join(format!(".Trash-{}", uid));
+    create_private_dir(&dir)?;
+    let meta = dir.symlink_metadata()?;
+    if !meta.is_dir() || meta.uid() != uid {
+        anyhow::bail!(
+            "Refusing to use untrusted trash directory {}",
+            dir.display()
+        );
+    }
+    Ok(dir)
+}
+
+#[cfg(not(unix))]
+fn trash_dir_for(_path: &Path) -> Result<(PathBuf, Option<PathBuf>)> {
+    let home_trash = home_trash_dir().context("Could not determine home trash directory")?;
+    Ok((home_trash, None))
+}
+
+/// Create a directory (and parents) only accessible by the current user
+fn create_private_dir(dir: &Path) -> Result<()> {
+    if dir.is_dir() {
+        return Ok(());
+    }
+
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder
+        .create(dir)
+        .with_context(|| format!("Failed to create trash directory {}", dir.display()))
+}
+
+/// `name`, then `stem.2.ext`, `stem.3.ext`, ...
+fn unique_name(file_name: &str, counter: u32) -> String {
+    if counter == 1 {
+        return file_name.to_string();
+    }
+    match file_name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => format!("{}.{}.{}", stem, counter, ext),
+        _ => format!("{}.{}", file_name, counter),
+    }
+}
+
+/// Make a path absolute without resolving the final component
+fn absolute(path: &Path) -> Result<PathBuf> {
+    let name = path.file_name().context("File has no name")?;
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let parent = parent
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {}", parent.display()))?;
+    Ok(parent.join(name))
+}
+
+/// Percent-encode a path for the `Path=` key of a `.trashinfo` file
+fn encode_path(path: &Path) -> String {
+    #[cfg(unix)]
+    let bytes = {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    };
+    #[cfg(not(unix))]
+    let bytes = path.to_string_lossy().as_bytes().to_vec();
+
+    let mut encoded = String::with_capacity(bytes.len());
+    for byte in bytes {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_move_to_trash_writes_info_and_unique_names() {
+        let dir = tempdir().unwrap();
+        let trash_dir = dir.path().join("Trash");
+
+        let first = dir.path().join("report 1.txt");
+        std::fs::write(&first, "one").unwrap();
+        let item1 = move_to_trash(&first, &trash_dir, None).unwrap();
+
+        std::fs::write(&first, "two").unwrap();
+        let item2 = move_to_trash(&first, &trash_dir, None).unwrap();
+
+        assert_eq!(item1.name, "report 1.txt");
+        assert_eq!(item2.name, "report 1.2.txt");
+        assert!(!first.exists());
+        assert_eq!(std::fs::read_to_string(item1.file_path()).unwrap(), "one");
+        assert_eq!(std::fs::read_to_string(item2.file_path()).unwrap(), "two");
+
+        let info = std::fs::read_to_string(item1.info_path()).unwrap();
+        assert!(info.starts_with("[Trash Info]\n"));
+        assert!(info.contains(&format!("Path={}\n", encode_path(&first))));
+        assert!(info.contains("report%201.txt"));
+        assert!(info.contains("DeletionDate="));
+    }
+
+    #[test]
+    fn test_per_mount_trash_stores_relative_path() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("docs").join("a.txt");
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, "a").unwrap();
+
+        let trash_dir = dir.path().join(".Trash-1000");
+        let item = move_to_trash(&file, &trash_dir, Some(dir.path())).unwrap();
+
+        let info = std::fs::read_to_string(item.info_path()).unwrap();
+        assert!(info.contains("Path=docs/a.txt\n"));
+        assert_eq!(item.original, file);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_mount_trash_dir_unusable() {
+        let dir = tempdir().unwrap();
+        let uid = unsafe { libc::getuid() };
+        assert_eq!(
+            mount_trash_dir(dir.path(), uid).unwrap(),
+            dir.path().join(format!(".Trash-{}", uid))
+        );
+
+        // Something else in the way of .Trash-$uid: the caller falls back
+        // to the home trash
+        let other = tempdir().unwrap();
+        std::fs::write(other.path().join(format!(".Trash-{}", uid)), "").unwrap();
+        assert!(mount_trash_dir(other.path(), uid).is_err());
+    }
+
+    #[test]
+    fn test_index_and_restore() {
+        let dir = tempdir().unwrap();
+        let index = dir.path().join("state").join("trash.jsonl");
+        let trash_dir = dir.path().join("Trash");
+        let file = dir.path().join("notes.md");
+        std::fs::write(&file, "notes").unwrap();
+
+        let item = move_to_trash(&file, &trash_dir, None).unwrap();
+        record(&index, &item).unwrap();
+        assert_eq!(list_in(&index).unwrap(), vec![item.clone()]);
+
+        std::fs::rename(item.file_path(), &file).unwrap();
+        assert!(list_in(&index).unwrap().is_empty());
+        std::fs::rename(&file, item.file_path()).unwrap();
+
+        // Restoring refuses to overwrite
+        std::fs::write(&file, "new").unwrap();
+        assert!(put_back(&item).is_err());
+        std::fs::remove_file(&file).unwrap();
+
+        put_back(&item).unwrap();
+        forget(&index, &item).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "notes");
+        assert!(!item.info_path().exists());
+        assert!(list_in(&index).unwrap().is_empty());
+    }
+}
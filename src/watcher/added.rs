@@ -0,0 +1,220 @@
+//! Tracking when files first appeared in a watched folder
+//!
+//! Browsers and archive tools keep a download's original modification time, so
+//! mtime says nothing about when a file arrived. The watcher records the first
+//! time it sees each path and persists that in the state directory, so "added
+//! more than N days ago" rules survive daemon restarts.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, warn};
+
+use crate::Config;
+use crate::rules::AgeTimestamp;
+
+/// Minimum time between writes of the index
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Persistent map of paths to the time they were added to their folder
+#[derive(Debug)]
+pub struct AddedIndex {
+    /// Index file (None keeps the index in memory only)
+    file: Option<PathBuf>,
+    entries: HashMap<PathBuf, DateTime<Local>>,
+    dirty: bool,
+    last_save: Instant,
+}
+
+impl AddedIndex {
+    /// Load the index from the state directory
+    pub fn load_default() -> Self {
+        match Config::state_dir() {
+            Some(dir) => Self::load(&dir.join("added.json")),
+            None => Self::in_memory(),
+        }
+    }
+
+    /// Load an index file (a missing or unreadable file starts an empty index)
+    pub fn load(file: &Path) -> Self {
+        let entries = match fs::read_to_string(file) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                warn!(
+                    "Ignoring invalid added-date index {}: {}",
+                    file.display(),
+                    e
+                );
+                HashMap::new()
+            }),
+            Err(e) if e.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                warn!("Failed to read {}: {}", file.display(), e);
+                HashMap::new()
+            }
+        };
+
+        Self {
+            file: Some(file.to_path_buf()),
+            entries,
+            dirty: false,
+            last_save: Instant::now(),
+        }
+    }
+
+    /// An index that is never written to disk
+    pub fn in_memory() -> Self {
+        Self {
+            file: None,
+            entries: HashMap::new(),
+            dirty: false,
+            last_save: Instant::now(),
+        }
+    }
+
+    /// When a path was added, if it has been seen
+    pub fn get(&self, path: &Path) -> Option<SystemTime> {
+        self.entries.get(path).map(|time| (*time).into())
+    }
+
+    /// Record that a path was just created, replacing any earlier entry
+    pub fn record_created(&mut self, path: &Path) -> SystemTime {
+        let now = SystemTime::now();
+        debug!("Created: {}", path.display());
+        self.entries.insert(path.to_path_buf(), now.into());
+        self.dirty = true;
+        now
+    }
+
+    /// Forget a path that was removed or renamed away
+    pub fn forget(&mut self, path: &Path) {
+        if self.entries.remove(path).is_some() {
+            self.dirty = true;
+        }
+    }
+
+    /// Record a path that was already there when first seen
+    ///
+    /// Its added date is estimated from the file's status change time, which
+    /// moving or copying it into the folder updates.
+    pub fn record_existing(&mut self, path: &Path) -> SystemTime {
+        let estimate = path
+            .symlink_metadata()
+            .and_then(|meta| AgeTimestamp::Added.of(&meta))
+            .map(|time| time.min(SystemTime::now()))
+            .unwrap_or_else(|_| SystemTime::now());
+        self.record(path, estimate)
+    }
+
+    fn record(&mut self, path: &Path, time: SystemTime) -> SystemTime {
+        if let Some(existing) = self.entries.get(path) {
+            return (*existing).into();
+        }
+        debug!("First seen: {}", path.display());
+        self.entries.insert(path.to_path_buf(), time.into());
+        self.dirty = true;
+        time
+    }
+
+    /// Write the index if it changed and wasn't saved recently
+    pub fn save_if_due(&mut self) {
+        if self.dirty
+            && self.last_save.elapsed() >= SAVE_INTERVAL
+            && let Err(e) = self.save()
+        {
+            warn!("Failed to save added-date index: {:#}", e);
+        }
+    }
+
+    /// Write the index, dropping paths that no longer exist
+    pub fn save(&mut self) -> Result<()> {
+        self.last_save = Instant::now();
+        let Some(ref file) = self.file else {
+            return Ok(());
+        };
+
+        self.entries
+            .retain(|path, _| path.symlink_metadata().is_ok());
+
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Write a temporary file first so a crash never leaves a truncated index
+        let partial = file.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_string(&self.entries)?)
+            .with_context(|| format!("Failed to write {}", partial.display()))?;
+        fs::rename(&partial, file)
+            .with_context(|| format!("Failed to write {}", file.display()))?;
+
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+impl Drop for AddedIndex {
+    fn drop(&mut self) {
+        if self.dirty
+            && let Err(e) = self.save()
+        {
+            warn!("Failed to save added-date index: {:#}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_seen_time_persists() {
+        let temp = tempfile::tempdir().unwrap();
+        let index_file = temp.path().join("state").join("added.json");
+        let file = temp.path().join("download.zip");
+        std::fs::write(&file, "data").unwrap();
+        let gone = temp.path().join("gone.txt");
+
+        let first = {
+            let mut index = AddedIndex::load(&index_file);
+            let first = index.record_created(&file);
+            index.record_created(&gone);
+            // Seeing it again keeps the original time
+            std::thread::sleep(Duration::from_millis(10));
+            assert_eq!(index.record_existing(&file), first);
+            index.save().unwrap();
+            first
+        };
+
+        let index = AddedIndex::load(&index_file);
+        let loaded = index.get(&file).unwrap();
+        let diff = loaded
+            .duration_since(first)
+            .unwrap_or_else(|e| e.duration());
+        assert!(diff < Duration::from_millis(1));
+        assert!(index.get(&gone).is_none());
+    }
+
+    #[test]
+    fn test_recreated_path_is_new() {
+        let temp = tempfile::tempdir().unwrap();
+        let file = temp.path().join("report.pdf");
+        std::fs::write(&file, "old").unwrap();
+
+        let mut index = AddedIndex::in_memory();
+        let first = index.record_created(&file);
+        std::thread::sleep(Duration::from_millis(10));
+
+        // Deleted and downloaded again under the same name
+        index.forget(&file);
+        assert!(index.get(&file).is_none());
+        let second = index.record_created(&file);
+        assert!(second > first);
+        assert_eq!(index.get(&file), Some(second));
+
+        // A create event without a remove in between also starts over
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(index.record_created(&file) > second);
+    }
+}
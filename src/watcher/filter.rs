@@ -0,0 +1,272 @@
+//! Which paths under a watch root are watched (exclude, .gitignore, max depth)
+
+use anyhow::{Context, Result};
+use glob::{MatchOptions, Pattern};
+use ignore::Match;
+use ignore::gitignore::Gitignore;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use tracing::warn;
+
+use crate::config::WatchConfig;
+
+/// The parts of a watch's folder tree that are watched
+#[derive(Debug)]
+pub struct WatchFilter {
+    root: PathBuf,
+    recursive: bool,
+    /// Deepest folder level below the root that is watched (None = unlimited)
+    max_depth: Option<usize>,
+    /// Exclude patterns without a `/`, matched against each name in the path
+    exclude_names: Vec<Pattern>,
+    /// Exclude patterns with a `/`, matched against the path from the root
+    exclude_paths: Vec<Pattern>,
+    /// Parsed .gitignore files by folder (None if gitignore isn't respected)
+    gitignores: Option<BTreeMap<PathBuf, Gitignore>>,
+}
+
+impl WatchFilter {
+    /// Build the filter for a watch, reading the root's .gitignore if needed
+    pub fn new(watch: &WatchConfig) -> Result<Self> {
+        let mut exclude_names = Vec::new();
+        let mut exclude_paths = Vec::new();
+        for pattern in &watch.exclude {
+            let trimmed = pattern.trim_matches('/');
+            let glob = Pattern::new(trimmed)
+                .with_context(|| format!("Invalid exclude pattern: {}", pattern))?;
+            if trimmed.contains('/') {
+                exclude_paths.push(glob);
+            } else {
+                exclude_names.push(glob);
+            }
+        }
+
+        let mut filter = Self {
+            root: crate::expand_path(&watch.path),
+            recursive: watch.recursive,
+            max_depth: if watch.recursive {
+                watch.max_depth
+            } else {
+                Some(0)
+            },
+            exclude_names,
+            exclude_paths,
+            gitignores: watch.respect_gitignore.then(BTreeMap::new),
+        };
+        let root = filter.root.clone();
+        filter.load_gitignore(&root);
+        Ok(filter)
+    }
+
+    /// The watch root
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether notify's recursive mode would watch folders this filter skips,
+    /// so the folders have to be registered one by one
+    pub fn needs_manual_recursion(&self) -> bool {
+        self.recursive
+            && (self.max_depth.is_some()
+                || !self.exclude_names.is_empty()
+                || !self.exclude_paths.is_empty()
+                || self.gitignores.is_some())
+    }
+
+    /// Whether a file or folder under the root is watched
+    ///
+    /// Paths outside the root are not.
+    pub fn allows(&self, path: &Path, is_dir: bool) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        let levels = relative.components().count();
+        if levels == 0 {
+            return true;
+        }
+
+        // A file sits on the level of the folder that holds it
+        let depth = if is_dir { levels } else { levels - 1 };
+        if self.max_depth.is_some_and(|max| depth > max) {
+            return false;
+        }
+
+        !self.is_excluded(relative) && !self.is_gitignored(path, relative, is_dir)
+    }
+
+    /// Check the path and each of its parent folders against `exclude`
+    fn is_excluded(&self, relative: &Path) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        let mut prefix = PathBuf::new();
+        for component in relative.components() {
+            prefix.push(component);
+            let name = component.as_os_str().to_string_lossy();
+            if self.exclude_names.iter().any(|p| p.matches(&name))
+                || self
+                    .exclude_paths
+                    .iter()
+                    .any(|p| p.matches_path_with(&prefix, options))
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn is_gitignored(&self, path: &Path, relative: &Path, is_dir: bool) -> bool {
+        let Some(ref gitignores) = self.gitignores else {
+            return false;
+        };
+        if relative
+            .components()
+            .any(|c| c == Component::Normal(".git".as_ref()))
+        {
+            return true;
+        }
+
+        // Deeper .gitignore files take precedence over the ones above them
+        for (dir, gitignore) in gitignores.iter().rev() {
+            if path == dir || !path.starts_with(dir) {
+                continue;
+            }
+            match gitignore.matched_path_or_any_parents(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+
+    /// Read (or forget) the .gitignore file in a folder
+    pub fn load_gitignore(&mut self, dir: &Path) {
+        let Some(ref mut gitignores) = self.gitignores else {
+            return;
+        };
+        let file = dir.join(".gitignore");
+        if !file.is_file() {
+            gitignores.remove(dir);
+            return;
+        }
+
+        let (gitignore, error) = Gitignore::new(&file);
+        if let Some(e) = error {
+            warn!("Problem reading {}: {}", file.display(), e);
+        }
+        gitignores.insert(dir.to_path_buf(), gitignore);
+    }
+
+    /// A folder and the subfolders under it that are watched, reading their
+    /// .gitignore files on the way
+    ///
+    /// Symlinked folders are not followed.
+    pub fn folders(&mut self, from: &Path) -> Vec<PathBuf> {
+        let mut folders = Vec::new();
+        let mut pending = vec![from.to_path_buf()];
+
+        while let Some(dir) = pending.pop() {
+            self.load_gitignore(&dir);
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    warn!("Failed to read {}: {}", dir.display(), e);
+                    continue;
+                }
+            };
+            for entry in entries.flatten() {
+                if entry.file_type().is_ok_and(|t| t.is_dir()) && self.allows(&entry.path(), true) {
+                    pending.push(entry.path());
+                }
+            }
+            folders.push(dir);
+        }
+
+        folders
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watch(root: &Path) -> WatchConfig {
+        WatchConfig {
+            path: root.to_path_buf(),
+            recursive: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_exclude_and_depth() {
+        let root = Path::new("/home/u/Projects");
+        let filter = WatchFilter::new(&WatchConfig {
+            exclude: vec!["node_modules".into(), "*.tmp".into(), "site/build/".into()],
+            max_depth: Some(2),
+            ..watch(root)
+        })
+        .unwrap();
+        let allows = |path: &str, is_dir: bool| filter.allows(&root.join(path), is_dir);
+
+        assert!(filter.needs_manual_recursion());
+        assert!(allows("app/src/main.rs", false));
+        assert!(!allows("app/node_modules", true));
+        assert!(!allows("app/node_modules/x/index.js", false));
+        assert!(!allows("notes.tmp", false));
+        assert!(!allows("site/build/index.html", false));
+        // Patterns with a slash are anchored at the root
+        assert!(allows("other/site/build", false));
+        assert!(allows("app/src", true));
+        assert!(!allows("app/src/bin", true));
+        assert!(!allows("app/src/bin/tool.rs", false));
+        assert!(!filter.allows(Path::new("/elsewhere/a.txt"), false));
+
+        let flat = WatchFilter::new(&WatchConfig {
+            recursive: false,
+            ..watch(root)
+        })
+        .unwrap();
+        assert!(flat.allows(&root.join("a.txt"), false));
+        assert!(!flat.allows(&root.join("sub"), true));
+        assert!(!flat.needs_manual_recursion());
+
+        assert!(
+            WatchFilter::new(&WatchConfig {
+                exclude: vec!["[".into()],
+                ..watch(root)
+            })
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_respect_gitignore() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::create_dir_all(root.join("web/dist")).unwrap();
+        fs::create_dir_all(root.join(".git/objects")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        fs::write(root.join("web/.gitignore"), "dist/\n!keep.log\n").unwrap();
+
+        let mut filter = WatchFilter::new(&WatchConfig {
+            respect_gitignore: true,
+            ..watch(root)
+        })
+        .unwrap();
+        let mut folders = filter.folders(root);
+        folders.sort();
+        assert_eq!(folders, vec![root.to_path_buf(), root.join("web")]);
+
+        assert!(!filter.allows(&root.join("target/debug/app"), false));
+        assert!(!filter.allows(&root.join("build.log"), false));
+        assert!(!filter.allows(&root.join("web/dist/app.js"), false));
+        assert!(filter.allows(&root.join("web/keep.log"), false));
+        assert!(filter.allows(&root.join("web/index.html"), false));
+        assert!(!filter.allows(&root.join(".git/HEAD"), false));
+    }
+}
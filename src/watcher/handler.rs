@@ -2,46 +2,151 @@
 
 use notify::Event;
 use std::collections::HashMap;
-use std::path::PathBuf;
-use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 
-/// Debounces file system events to avoid processing the same file multiple times
+/// Extensions browsers and download tools use while a file is still arriving
+///
+/// These files are renamed to their final name once complete, which produces
+/// an event for the real file.
+const IN_PROGRESS_EXTENSIONS: &[&str] = &["part", "crdownload", "download"];
+
+/// Size and modification time of a file, compared by the stability check
+type Sample = (u64, Option<SystemTime>);
+
+/// Debounces file system events so a file is processed once it stops changing
+///
+/// A path is handed out only after no event arrived for it during the debounce
+/// window (trailing edge). With a stability check, its size and modification
+/// time must also stay the same across several samples.
 pub struct EventHandler {
-    /// Recent events by path
+    /// Paths with events that haven't been processed yet
+    pending: HashMap<PathBuf, Pending>,
+
+    /// When paths were last handed out for processing
     recent: HashMap<PathBuf, Instant>,
 
     /// Debounce duration
     debounce: Duration,
+
+    /// Identical samples needed before a file counts as complete (0 = off)
+    stability_samples: u32,
+
+    /// Time between stability samples
+    sample_interval: Duration,
+}
+
+/// A path waiting to become quiet and stable
+struct Pending {
+    last_event: Instant,
+    last_sample: Option<(Instant, Sample)>,
+    /// Consecutive identical samples so far
+    stable: u32,
 }
 
 impl EventHandler {
     /// Create a new event handler with the given debounce duration
     pub fn new(debounce_seconds: u64) -> Self {
         Self {
+            pending: HashMap::new(),
             recent: HashMap::new(),
             debounce: Duration::from_secs(debounce_seconds),
+            stability_samples: 0,
+            sample_interval: Duration::from_secs(1),
         }
     }
 
-    /// Check if an event should be processed (returns true if not recently seen)
-    pub fn should_process(&mut self, event: &Event) -> Vec<PathBuf> {
-        let now = Instant::now();
-        let mut paths_to_process = Vec::new();
+    /// Require a file's size and modification time to stay the same across
+    /// `samples` checks, one second apart, before it is processed
+    pub fn set_stability_samples(&mut self, samples: u32) {
+        self.stability_samples = samples;
+    }
 
+    /// Note the paths an event touched, restarting their debounce window
+    ///
+    /// Paths of in-progress downloads are ignored until they get their final
+    /// name.
+    pub fn record(&mut self, event: &Event) {
+        let now = Instant::now();
         for path in &event.paths {
-            let should_process = self
-                .recent
-                .get(path)
-                .map(|&last| now.duration_since(last) > self.debounce)
-                .unwrap_or(true);
-
-            if should_process {
-                self.recent.insert(path.clone(), now);
-                paths_to_process.push(path.clone());
+            if is_in_progress(path) {
+                continue;
+            }
+            self.pending.insert(
+                path.clone(),
+                Pending {
+                    last_event: now,
+                    last_sample: None,
+                    stable: 0,
+                },
+            );
+        }
+    }
+
+    /// Take the paths that have been quiet (and stable) long enough
+    ///
+    /// Paths the stability check can't sample any more (deleted or renamed)
+    /// are dropped.
+    pub fn ready(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        let mut ready = Vec::new();
+
+        self.pending.retain(|path, pending| {
+            if now.duration_since(pending.last_event) < self.debounce {
+                return true;
             }
+
+            if self.stability_samples > 0 {
+                if pending
+                    .last_sample
+                    .is_some_and(|(at, _)| now.duration_since(at) < self.sample_interval)
+                {
+                    return true;
+                }
+                let Some(sample) = sample(path) else {
+                    return false;
+                };
+                pending.stable = match pending.last_sample {
+                    Some((_, previous)) if previous == sample => pending.stable + 1,
+                    _ => 1,
+                };
+                pending.last_sample = Some((now, sample));
+                if pending.stable < self.stability_samples {
+                    return true;
+                }
+            }
+
+            ready.push(path.clone());
+            false
+        });
+
+        for path in &ready {
+            self.recent.insert(path.clone(), now);
+        }
+        ready.sort();
+        ready
+    }
+
+    /// Check if a path found by a scan should be processed, marking it if so
+    ///
+    /// Paths waiting on events, and paths processed within the debounce window,
+    /// are skipped so a scan doesn't race the event that is handling them.
+    pub fn should_process_path(&mut self, path: &Path) -> bool {
+        if self.pending.contains_key(path) || is_in_progress(path) {
+            return false;
         }
 
-        paths_to_process
+        let now = Instant::now();
+        let should_process = self
+            .recent
+            .get(path)
+            .map(|&last| now.duration_since(last) > self.debounce)
+            .unwrap_or(true);
+
+        if should_process {
+            self.recent.insert(path.to_path_buf(), now);
+        }
+        should_process
     }
 
     /// Clean up old entries (call periodically)
@@ -54,27 +159,116 @@ impl EventHandler {
     }
 }
 
+/// Whether a path is a download that is still being written
+fn is_in_progress(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            IN_PROGRESS_EXTENSIONS
+                .iter()
+                .any(|partial| ext.eq_ignore_ascii_case(partial))
+        })
+}
+
+/// Current size and modification time of a file
+fn sample(path: &Path) -> Option<Sample> {
+    let meta = path.metadata().ok()?;
+    Some((meta.len(), meta.modified().ok()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use notify::EventKind;
+    use std::thread::sleep;
+
+    fn event(paths: &[&Path]) -> Event {
+        Event {
+            kind: EventKind::Create(notify::event::CreateKind::File),
+            paths: paths.iter().map(|p| p.to_path_buf()).collect(),
+            attrs: Default::default(),
+        }
+    }
 
     #[test]
-    fn test_debounce() {
+    fn test_trailing_edge_debounce() {
+        // Checks stay 200ms clear of the debounce so a busy machine can't flip them
         let mut handler = EventHandler::new(1);
+        handler.debounce = Duration::from_millis(500);
+        let path = Path::new("/tmp/test.txt");
 
-        let event = Event {
-            kind: EventKind::Create(notify::event::CreateKind::File),
-            paths: vec![PathBuf::from("/tmp/test.txt")],
-            attrs: Default::default(),
-        };
+        // Nothing fires while events keep arriving, even once the first event
+        // is older than the debounce
+        handler.record(&event(&[path]));
+        assert!(handler.ready().is_empty());
+        sleep(Duration::from_millis(300));
+        handler.record(&event(&[path]));
+        sleep(Duration::from_millis(300));
+        assert!(handler.ready().is_empty());
+
+        // Fires once quiet, and only once
+        sleep(Duration::from_millis(400));
+        assert_eq!(handler.ready(), vec![path.to_path_buf()]);
+        assert!(handler.ready().is_empty());
+
+        // A scan right after doesn't process it again
+        assert!(!handler.should_process_path(path));
+    }
+
+    #[test]
+    fn test_in_progress_downloads_ignored() {
+        let mut handler = EventHandler::new(0);
+        let partial = Path::new("/tmp/video.mp4.crdownload");
+        let done = Path::new("/tmp/video.mp4");
+
+        handler.record(&event(&[partial]));
+        handler.record(&event(&[Path::new("/tmp/file.part")]));
+        assert!(handler.ready().is_empty());
 
-        // First event should be processed
-        let paths = handler.should_process(&event);
-        assert_eq!(paths.len(), 1);
+        // The final rename reports both names
+        handler.record(&event(&[partial, done]));
+        assert_eq!(handler.ready(), vec![done.to_path_buf()]);
+    }
+
+    #[test]
+    fn test_stability_check() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("growing.bin");
+        std::fs::write(&path, "a").unwrap();
+
+        let mut handler = EventHandler::new(0);
+        handler.set_stability_samples(3);
+        handler.sample_interval = Duration::ZERO;
+        handler.record(&event(&[&path]));
+
+        // First two samples aren't enough yet
+        assert!(handler.ready().is_empty());
+        assert!(handler.ready().is_empty());
+        // A size change restarts the count
+        std::fs::write(&path, "abc").unwrap();
+        assert!(handler.ready().is_empty());
+        assert!(handler.ready().is_empty());
+        assert_eq!(handler.ready(), vec![path.clone()]);
+
+        // Files that disappear are dropped
+        let gone = temp.path().join("gone.bin");
+        handler.record(&event(&[&gone]));
+        assert!(handler.ready().is_empty());
+        assert!(handler.pending.is_empty());
+    }
+
+    #[test]
+    fn test_single_stability_sample() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("done.bin");
+        std::fs::write(&path, "a").unwrap();
+        let gone = temp.path().join("gone.bin");
 
-        // Immediate second event should be debounced
-        let paths = handler.should_process(&event);
-        assert_eq!(paths.len(), 0);
+        // One sample doesn't wait, but still skips files that are gone
+        let mut handler = EventHandler::new(0);
+        handler.set_stability_samples(1);
+        handler.record(&event(&[&path, &gone]));
+        assert_eq!(handler.ready(), vec![path]);
+        assert!(handler.pending.is_empty());
     }
 }
@@ -0,0 +1,140 @@
+//! Health of each watch root, shared with `hazelnut status` and the TUI
+//!
+//! Roots on removable drives or network mounts come and go. The daemon keeps
+//! a watch whose root is missing and retries it, and writes the state of every
+//! watch to the state directory so other processes can show it.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Config;
+
+/// Whether a watch root is being monitored
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", content = "message", rename_all = "lowercase")]
+pub enum WatchHealth {
+    /// Registered and receiving events
+    Active,
+    /// The root doesn't exist (deleted, or the drive isn't mounted)
+    Missing,
+    /// The root exists but couldn't be watched
+    Error(String),
+}
+
+impl WatchHealth {
+    /// Short lowercase name (active, missing, error)
+    pub fn label(&self) -> &'static str {
+        match self {
+            WatchHealth::Active => "active",
+            WatchHealth::Missing => "missing",
+            WatchHealth::Error(_) => "error",
+        }
+    }
+}
+
+impl fmt::Display for WatchHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchHealth::Error(message) => write!(f, "error: {}", message),
+            other => f.write_str(other.label()),
+        }
+    }
+}
+
+/// The health of one watch and when it last changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchStatus {
+    /// Watch root (expanded)
+    pub path: PathBuf,
+    pub health: WatchHealth,
+    pub since: DateTime<Local>,
+}
+
+impl fmt::Display for WatchStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let icon = match self.health {
+            WatchHealth::Active => "✓",
+            _ => "✗",
+        };
+        write!(
+            f,
+            "{} {} ({} since {})",
+            icon,
+            self.path.display(),
+            self.health,
+            self.since.format("%Y-%m-%d %H:%M:%S")
+        )
+    }
+}
+
+impl WatchStatus {
+    /// Status file in the state directory
+    pub fn default_file() -> Option<PathBuf> {
+        Config::state_dir().map(|dir| dir.join("watches.json"))
+    }
+
+    /// Read the statuses the daemon last wrote (empty if there are none)
+    pub fn load(file: &Path) -> Vec<WatchStatus> {
+        fs::read_to_string(file)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Write the statuses of all watches
+    pub fn save(file: &Path, statuses: &[WatchStatus]) -> Result<()> {
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Readers poll this file, so never let them see it half written
+        let partial = file.with_extension("json.partial");
+        fs::write(&partial, serde_json::to_string_pretty(statuses)?)
+            .with_context(|| format!("Failed to write {}", partial.display()))?;
+        fs::rename(&partial, file).with_context(|| format!("Failed to write {}", file.display()))
+    }
+}
+
+/// Print the watch health the daemon last reported, for `hazelnut status`
+pub fn print_statuses() {
+    let statuses = WatchStatus::default_file()
+        .map(|file| WatchStatus::load(&file))
+        .unwrap_or_default();
+    if !statuses.is_empty() {
+        println!("   Watches:");
+    }
+    for status in statuses {
+        println!("     {}", status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_file_roundtrip() {
+        let temp = tempfile::tempdir().unwrap();
+        let file = temp.path().join("state/watches.json");
+        assert!(WatchStatus::load(&file).is_empty());
+
+        let statuses = vec![
+            WatchStatus {
+                path: PathBuf::from("/media/usb"),
+                health: WatchHealth::Missing,
+                since: Local::now(),
+            },
+            WatchStatus {
+                path: PathBuf::from("/srv/share"),
+                health: WatchHealth::Error("Permission denied".into()),
+                since: Local::now(),
+            },
+        ];
+        WatchStatus::save(&file, &statuses).unwrap();
+        assert_eq!(WatchStatus::load(&file), statuses);
+        assert_eq!(statuses[1].health.to_string(), "error: Permission denied");
+    }
+}
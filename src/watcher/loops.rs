@@ -0,0 +1,288 @@
+//! Keeping the watcher from reacting to its own changes
+//!
+//! A rule that moves a file into another watched folder, or renames it in
+//! place, produces events for the new path. Without care those events run the
+//! rules again, and a rename rule keeps appending to the same name. Files
+//! `hazelnut undo` puts back would likewise be sent straight through the rule
+//! that was undone.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::journal::{self, RestoredFile};
+use crate::rules::ProcessReport;
+
+/// Paths the watcher's own actions produced, whose events are ignored for a
+/// while
+pub struct OwnChanges {
+    /// Produced paths and when their events stop being ignored
+    paths: HashMap<PathBuf, Instant>,
+    /// How long events are ignored after an action
+    window: Duration,
+    /// Files undo put back, from the list it writes (None = not read)
+    restored_file: Option<PathBuf>,
+    /// Modification time and size of the list when it was last read
+    restored_read: Option<(SystemTime, u64)>,
+    restored: Vec<RestoredFile>,
+}
+
+impl OwnChanges {
+    /// Ignore events for `window` after each action
+    pub fn new(window: Duration) -> Self {
+        Self {
+            paths: HashMap::new(),
+            window,
+            restored_file: None,
+            restored_read: None,
+            restored: Vec::new(),
+        }
+    }
+
+    /// Read the list of files `hazelnut undo` put back from this file
+    pub fn set_restored_file(&mut self, file: Option<PathBuf>) {
+        self.restored_file = file;
+        self.restored_read = None;
+        self.restored.clear();
+    }
+
+    /// Whether undo put a file back and it hasn't changed since
+    ///
+    /// Undo writes the list before moving a file, so the list is read again
+    /// whenever it changes.
+    pub fn is_restored(&mut self, path: &Path) -> bool {
+        if let Some(ref file) = self.restored_file {
+            let stamp = fs::metadata(file)
+                .and_then(|m| Ok((m.modified()?, m.len())))
+                .ok();
+            if stamp != self.restored_read {
+                self.restored = journal::load_restored(file);
+                self.restored_read = stamp;
+            }
+        }
+        // Once the file changes it is handled like any other
+        self.restored.retain(|r| r.path != path || r.is_unchanged());
+        self.restored.iter().any(|r| r.path == path)
+    }
+
+    /// Remember the paths a processing run moved files to or created
+    ///
+    /// A file an action left in place isn't remembered, so a later change to
+    /// it is still processed.
+    pub fn remember(&mut self, report: &ProcessReport) {
+        let until = Instant::now() + self.window;
+        for applied in &report.applied {
+            let moved = applied.result.iter().filter(|r| **r != applied.path);
+            for path in moved.chain(&applied.created) {
+                self.paths.insert(path.clone(), until);
+            }
+        }
+    }
+
+    /// Whether an event for a path (or anything inside a created folder) came
+    /// from the watcher's own actions
+    pub fn contains(&self, path: &Path) -> bool {
+        let now = Instant::now();
+        path.ancestors()
+            .any(|p| self.paths.get(p).is_some_and(|&until| until > now))
+    }
+
+    /// Forget paths whose window has passed
+    pub fn cleanup(&mut self) {
+        let now = Instant::now();
+        self.paths.retain(|_, until| *until > now);
+    }
+}
+
+/// Stops processing a file that rules keep acting on
+///
+/// Each file's history follows it through moves and renames, so a rule that
+/// renames a file over and over is caught even though the name changes.
+pub struct LoopDetector {
+    /// Runs allowed on one file within the window (0 = unlimited)
+    limit: usize,
+    window: Duration,
+    /// Recent runs on each file (when, and which rules acted), by current path
+    history: HashMap<PathBuf, VecDeque<(Instant, Vec<String>)>>,
+    /// Files that won't be processed again
+    stopped: HashSet<PathBuf>,
+}
+
+impl LoopDetector {
+    /// Allow `limit` runs that act on a file within `window`
+    pub fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            history: HashMap::new(),
+            stopped: HashSet::new(),
+        }
+    }
+
+    /// Whether a file was stopped for looping
+    pub fn is_stopped(&self, path: &Path) -> bool {
+        self.stopped.contains(path)
+    }
+
+    /// Note a processing run of `path`
+    ///
+    /// Returns the rules involved if the file went over the limit; it is then
+    /// stopped until the watcher is recreated (on reload or restart).
+    pub fn record(&mut self, path: &Path, report: &ProcessReport) -> Option<Vec<String>> {
+        if self.limit == 0 || report.applied.is_empty() {
+            return None;
+        }
+
+        let now = Instant::now();
+        let mut runs = self.history.remove(path).unwrap_or_default();
+        runs.retain(|(at, _)| now.duration_since(*at) < self.window);
+        runs.push_back((now, report.applied.iter().map(|a| a.rule.clone()).collect()));
+
+        // Where the file ended up (a failed step leaves it where the step ran)
+        let current = match &report.failure {
+            Some(failure) => Some(failure.path.clone()),
+            None => report.applied.last().and_then(|a| a.result.clone()),
+        };
+        let current = current?;
+
+        if runs.len() > self.limit {
+            let mut rules: Vec<String> = Vec::new();
+            for rule in runs.iter().flat_map(|(_, rules)| rules) {
+                if !rules.contains(rule) {
+                    rules.push(rule.clone());
+                }
+            }
+            self.stopped.insert(current);
+            return Some(rules);
+        }
+
+        self.history.insert(current, runs);
+        None
+    }
+
+    /// Forget files with no runs inside the window, and stopped files that
+    /// are gone
+    pub fn cleanup(&mut self) {
+        let now = Instant::now();
+        let window = self.window;
+        self.history.retain(|_, runs| {
+            runs.back()
+                .is_some_and(|(at, _)| now.duration_since(*at) < window)
+        });
+        self.stopped.retain(|path| path.symlink_metadata().is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{Action, AppliedRule};
+
+    fn renamed(rule: &str, from: &str, to: &str) -> ProcessReport {
+        ProcessReport {
+            applied: vec![AppliedRule {
+                rule: rule.to_string(),
+                step: 0,
+                action: Action::Nothing,
+                path: PathBuf::from(from),
+                result: Some(PathBuf::from(to)),
+                created: None,
+            }],
+            failure: None,
+        }
+    }
+
+    #[test]
+    fn test_own_changes() {
+        let mut own = OwnChanges::new(Duration::from_secs(60));
+        let mut report = renamed("date", "/w/a.txt", "/w/2024_a.txt");
+        report.applied[0].created = Some(PathBuf::from("/w/extracted"));
+        own.remember(&report);
+
+        assert!(own.contains(Path::new("/w/2024_a.txt")));
+        assert!(own.contains(Path::new("/w/extracted/inner/file.txt")));
+        assert!(!own.contains(Path::new("/w/a.txt")));
+        assert!(!own.contains(Path::new("/w/other.txt")));
+
+        // Actions that leave the file where it is don't hide its later changes
+        own.remember(&renamed("tag", "/w/b.txt", "/w/b.txt"));
+        assert!(!own.contains(Path::new("/w/b.txt")));
+
+        let mut expired = OwnChanges::new(Duration::ZERO);
+        expired.remember(&report);
+        assert!(!expired.contains(Path::new("/w/2024_a.txt")));
+    }
+
+    #[test]
+    fn test_restored_files_skipped_until_modified() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let journal = dir.join("journal.jsonl");
+        let original = dir.join("a.pdf");
+        let moved = dir.join("docs.pdf");
+        std::fs::write(&moved, "pdf").unwrap();
+        let entry = journal::JournalEntry {
+            at: chrono::Local::now(),
+            rule: "PDFs".to_string(),
+            action: "move".to_string(),
+            source: original.clone(),
+            result: Some(moved),
+            created: None,
+            undone: false,
+        };
+        journal::record(&journal, &[entry]).unwrap();
+
+        let mut own = OwnChanges::new(Duration::from_secs(60));
+        own.set_restored_file(Some(journal::restored_path(&journal)));
+        assert!(!own.is_restored(&original));
+
+        // Undo writes the list while the daemon is running
+        let entries = journal::list_in(&journal).unwrap();
+        journal::undo(&journal, entries).unwrap();
+        assert!(own.is_restored(&original));
+        assert!(!own.is_restored(&dir.join("other.pdf")));
+
+        // Once the user changes the file, rules apply again
+        std::fs::write(&original, "edited").unwrap();
+        assert!(!own.is_restored(&original));
+    }
+
+    #[test]
+    fn test_loop_detector_follows_renames() {
+        let mut loops = LoopDetector::new(2, Duration::from_secs(60));
+        assert_eq!(
+            loops.record(Path::new("/w/a"), &renamed("date", "/w/a", "/w/b")),
+            None
+        );
+        assert_eq!(
+            loops.record(Path::new("/w/b"), &renamed("tag", "/w/b", "/w/c")),
+            None
+        );
+        assert!(!loops.is_stopped(Path::new("/w/c")));
+
+        // A third run on the same file, under its latest name, trips it
+        assert_eq!(
+            loops.record(Path::new("/w/c"), &renamed("date", "/w/c", "/w/d")),
+            Some(vec!["date".to_string(), "tag".to_string()])
+        );
+        assert!(loops.is_stopped(Path::new("/w/d")));
+
+        // Stopped files that no longer exist are forgotten
+        loops.cleanup();
+        assert!(!loops.is_stopped(Path::new("/w/d")));
+
+        // Other files are unaffected, and 0 turns detection off
+        assert_eq!(
+            loops.record(Path::new("/w/x"), &renamed("date", "/w/x", "/w/y")),
+            None
+        );
+        let mut off = LoopDetector::new(0, Duration::from_secs(60));
+        for _ in 0..10 {
+            assert_eq!(
+                off.record(Path::new("/w/a"), &renamed("date", "/w/a", "/w/a")),
+                None
+            );
+        }
+    }
+}
@@ -1,26 +1,119 @@
 //! File system watcher
 
+mod added;
+mod filter;
 mod handler;
+mod health;
+mod loops;
+mod scan;
 
+pub use added::AddedIndex;
+pub use filter::WatchFilter;
 pub use handler::EventHandler;
+pub use health::{WatchHealth, WatchStatus, print_statuses};
+pub use loops::{LoopDetector, OwnChanges};
+pub use scan::walk;
 
-use anyhow::Result;
-use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher as NotifyWatcher};
-use std::path::Path;
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local};
+use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+use notify::{Config, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher as NotifyWatcher};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
-use std::time::Duration;
-use tracing::{debug, error, info};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
 
-use crate::rules::RuleEngine;
+use crate::config::{WatchBackend, WatchConfig, rule_applies, watch_root};
+use crate::rules::{MatchContext, RuleEngine, Schedule};
+
+type EventSender = mpsc::Sender<Result<notify::Event, notify::Error>>;
 
 /// File system watcher that monitors directories and applies rules
 pub struct Watcher {
+    /// Native backend, shared by every watch that doesn't poll
     watcher: RecommendedWatcher,
     engine: RuleEngine,
+    /// Configured watches (paths already expanded), registered or not
+    watches: Vec<WatchConfig>,
+    /// Health of each configured watch, in the same order
+    statuses: Vec<WatchStatus>,
+    /// Where statuses are written for `hazelnut status` (None = not written)
+    status_file: Option<PathBuf>,
+    /// Statuses changed since they were last written
+    status_dirty: bool,
+    /// When [`Watcher::check_watches`] next looks at the roots
+    next_check: Instant,
+    /// Backend and filter of each registered watch
+    active: Vec<ActiveWatch>,
+    /// Sender for poll backends, which feed the same channel as the native one
+    tx: EventSender,
+    /// How often poll backends scan their folder
+    poll_interval: Duration,
     rx: mpsc::Receiver<Result<notify::Event, notify::Error>>,
     event_handler: EventHandler,
+    /// When files first appeared in a watched folder
+    added: AddedIndex,
+    /// Paths produced by the watcher's own actions
+    own: OwnChanges,
+    loops: LoopDetector,
+    /// Periodic re-scans of watch roots
+    scans: Vec<ScheduledScan>,
+    /// Scans in progress, worked through a few files at a time
+    queue: VecDeque<ScanBatch>,
+    /// Files checked per call of [`Watcher::run_scans`]
+    scan_rate: usize,
+}
+
+/// How a registered watch is monitored
+struct ActiveWatch {
+    filter: WatchFilter,
+    /// Folders registered individually (empty if notify watches recursively)
+    folders: Vec<PathBuf>,
+    /// The watch's own poll backend (None = the shared native backend)
+    poller: Option<PollWatcher>,
+    /// The root's identity when it was registered
+    identity: Option<RootIdentity>,
+}
+
+/// Device and inode of a watch root
+///
+/// These change when the folder is deleted and recreated, or when a different
+/// drive is mounted there, which leaves the old registration watching nothing.
+type RootIdentity = (u64, u64);
+
+/// A periodic re-scan of a watch root, from a watch's or a rule's `rescan`
+struct ScheduledScan {
+    root: PathBuf,
+    /// Only re-check this rule (None = every rule the watch allows)
+    rule: Option<String>,
+    schedule: Schedule,
+    /// When the scan runs next (None if the schedule has no more runs)
+    next: Option<DateTime<Local>>,
 }
 
+/// Files found by a scan that haven't been checked yet
+struct ScanBatch {
+    root: PathBuf,
+    files: VecDeque<PathBuf>,
+    /// Only check these rules (None = every rule that applies)
+    rules: Option<HashSet<String>>,
+}
+
+/// Files checked per call of [`Watcher::run_scans`] unless configured otherwise
+const DEFAULT_SCAN_RATE: usize = 50;
+
+/// How often watch roots are checked, and missing ones retried
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long events for paths the watcher's own actions produced are ignored,
+/// on top of two polling intervals (poll backends report changes late)
+const OWN_CHANGE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Runs per file and window of the loop detector unless configured otherwise
+const DEFAULT_LOOP_LIMIT: usize = 5;
+const DEFAULT_LOOP_WINDOW: Duration = Duration::from_secs(60);
+
 impl Watcher {
     /// Create a new watcher with the given rule engine, polling interval, and debounce duration
     pub fn new(
@@ -29,43 +122,315 @@ impl Watcher {
         debounce_seconds: u64,
     ) -> Result<Self> {
         let (tx, rx) = mpsc::channel();
+        let poll_interval = Duration::from_secs(polling_interval_secs);
 
-        let watcher = RecommendedWatcher::new(
-            move |res| {
-                if let Err(e) = tx.send(res) {
-                    error!("Failed to send watch event: {}", e);
-                }
-            },
-            Config::default().with_poll_interval(Duration::from_secs(polling_interval_secs)),
-        )?;
+        let watcher = RecommendedWatcher::new(forward_to(tx.clone()), Config::default())?;
 
         Ok(Self {
             watcher,
             engine,
+            watches: Vec::new(),
+            statuses: Vec::new(),
+            status_file: None,
+            status_dirty: false,
+            next_check: Instant::now(),
+            active: Vec::new(),
+            tx,
+            poll_interval,
             rx,
             event_handler: EventHandler::new(debounce_seconds),
+            added: AddedIndex::load_default(),
+            own: OwnChanges::new(poll_interval * 2 + OWN_CHANGE_WINDOW),
+            loops: LoopDetector::new(DEFAULT_LOOP_LIMIT, DEFAULT_LOOP_WINDOW),
+            scans: Vec::new(),
+            queue: VecDeque::new(),
+            scan_rate: DEFAULT_SCAN_RATE,
         })
     }
 
+    /// Only process a file once its size and modification time stayed the same
+    /// across `samples` checks, one second apart (0 turns the check off)
+    pub fn set_stability_samples(&mut self, samples: u32) {
+        self.event_handler.set_stability_samples(samples);
+    }
+
+    /// Limit how many scanned files each call of [`Watcher::run_scans`] checks
+    ///
+    /// The daemon calls it once a second, so this is files per second.
+    pub fn set_scan_rate(&mut self, files: usize) {
+        self.scan_rate = files.max(1);
+    }
+
+    /// Stop processing a file once rules acted on it more than `limit` times
+    /// within `window` (a limit of 0 turns this off)
+    pub fn set_loop_limit(&mut self, limit: usize, window: Duration) {
+        self.loops = LoopDetector::new(limit, window);
+    }
+
+    /// Skip files `hazelnut undo` put back, as listed in this file
+    pub fn set_restored_file(&mut self, file: Option<PathBuf>) {
+        self.own.set_restored_file(file);
+    }
+
+    /// Write watch statuses to this file whenever they change
+    pub fn set_status_file(&mut self, file: Option<PathBuf>) {
+        self.status_file = file;
+        self.status_dirty = true;
+    }
+
+    /// Health of every configured watch, in the order they were added
+    pub fn statuses(&self) -> &[WatchStatus] {
+        &self.statuses
+    }
+
     /// Start watching a directory
-    pub fn watch(&mut self, path: &Path, recursive: bool) -> Result<()> {
-        let mode = if recursive {
-            RecursiveMode::Recursive
-        } else {
-            RecursiveMode::NonRecursive
+    ///
+    /// Only the rules listed in `watch.rules` are applied to its files (all rules
+    /// if empty). With `scan_on_start`, the files already there are queued for
+    /// [`Watcher::run_scans`].
+    ///
+    /// A watch that can't be registered (its root is missing, say) is kept and
+    /// retried by [`Watcher::check_watches`]; the error is still returned so
+    /// the caller can report it.
+    pub fn watch(&mut self, watch: &WatchConfig) -> Result<()> {
+        let path = crate::expand_path(&watch.path);
+        let watch = WatchConfig {
+            path: path.clone(),
+            ..watch.clone()
+        };
+
+        let result = self.register(&watch);
+        let health = match &result {
+            Ok(()) => WatchHealth::Active,
+            Err(e) => health_after_error(&path, e),
+        };
+        self.schedule_scans(&path, &watch);
+        if watch.scan_on_start && result.is_ok() {
+            self.queue_scan(path.clone(), None);
+        }
+        self.statuses.push(WatchStatus {
+            path,
+            health,
+            since: Local::now(),
+        });
+        self.status_dirty = true;
+        self.watches.push(watch);
+
+        result
+    }
+
+    /// Register a watch's folders with its backend
+    ///
+    /// Recursive watches with `exclude`, `respect_gitignore` or `max_depth`
+    /// register each watched folder on its own, so excluded subtrees don't
+    /// produce events at all.
+    ///
+    /// Watches with `backend = "poll"` get their own poller that scans the
+    /// folder every polling interval, for mounts where native change
+    /// notifications don't arrive (NFS, SMB, sshfs).
+    fn register(&mut self, watch: &WatchConfig) -> Result<()> {
+        let path = &watch.path;
+        if !path.exists() {
+            bail!("{} does not exist", path.display());
+        }
+        let identity = root_identity(path);
+        let mut filter = WatchFilter::new(watch)?;
+        let mut poller = match watch.backend {
+            WatchBackend::Native => None,
+            WatchBackend::Poll => Some(PollWatcher::new(
+                forward_to(self.tx.clone()),
+                Config::default()
+                    .with_poll_interval(self.poll_interval)
+                    .with_compare_contents(watch.compare_contents),
+            )?),
         };
+        let backend = backend(&mut poller, &mut self.watcher);
 
-        self.watcher.watch(path, mode)?;
-        info!("Watching: {} (recursive: {})", path.display(), recursive);
+        let folders = if filter.needs_manual_recursion() {
+            let folders = filter.folders(path);
+            backend.watch(path, RecursiveMode::NonRecursive)?;
+            for folder in folders.iter().filter(|f| *f != path) {
+                if let Err(e) = backend.watch(folder, RecursiveMode::NonRecursive) {
+                    warn!("Failed to watch {}: {}", folder.display(), e);
+                }
+            }
+            folders
+        } else {
+            let mode = if watch.recursive {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            };
+            backend.watch(path, mode)?;
+            Vec::new()
+        };
+        info!(
+            "Watching: {} (recursive: {}, backend: {})",
+            path.display(),
+            watch.recursive,
+            watch.backend
+        );
+        if !folders.is_empty() {
+            debug!(
+                "Registered {} folders under {}",
+                folders.len(),
+                path.display()
+            );
+        }
 
+        self.active.push(ActiveWatch {
+            filter,
+            folders,
+            poller,
+            identity,
+        });
         Ok(())
     }
 
+    /// Remove a root's registration, keeping the watch itself
+    fn deregister(&mut self, path: &Path) -> Result<()> {
+        let mut result = Ok(());
+        for active in self.active.iter_mut().filter(|a| a.filter.root() == path) {
+            let folders = std::mem::take(&mut active.folders);
+            let backend = backend(&mut active.poller, &mut self.watcher);
+            if let Err(e) = backend.unwatch(path) {
+                result = Err(e.into());
+            }
+            for folder in folders.iter().filter(|f| *f != path) {
+                // Folders deleted since they were registered are already gone
+                let _ = backend.unwatch(folder);
+            }
+        }
+        self.active.retain(|a| a.filter.root() != path);
+        self.queue.retain(|batch| batch.root != path);
+        result
+    }
+
+    /// Notice watch roots that disappeared or were replaced, and retry the
+    /// ones that aren't registered
+    ///
+    /// A root that comes back is registered again and scanned, so files that
+    /// arrived while it was away are processed. Roots are checked every few
+    /// seconds (sooner after an event on a root); calling this more often is
+    /// cheap. Changed statuses are written to the status file.
+    pub fn check_watches(&mut self) {
+        let now = Instant::now();
+        if now < self.next_check {
+            return;
+        }
+        self.next_check = now + HEALTH_CHECK_INTERVAL;
+
+        for index in 0..self.watches.len() {
+            let path = self.watches[index].path.clone();
+            let identity = root_identity(&path);
+            if let Some(active) = self.active.iter().find(|a| a.filter.root() == path) {
+                if identity.is_some() && active.identity == identity {
+                    continue;
+                }
+                // The registration points at a folder that's gone
+                let _ = self.deregister(&path);
+            }
+
+            if identity.is_none() {
+                self.set_health(index, WatchHealth::Missing);
+                continue;
+            }
+            let watch = self.watches[index].clone();
+            match self.register(&watch) {
+                Ok(()) => {
+                    self.set_health(index, WatchHealth::Active);
+                    self.queue_scan(path, None);
+                }
+                Err(e) => {
+                    let health = health_after_error(&path, &e);
+                    self.set_health(index, health);
+                }
+            }
+        }
+
+        if self.status_dirty {
+            self.save_statuses();
+        }
+    }
+
+    /// Update a watch's health, logging the change
+    fn set_health(&mut self, index: usize, health: WatchHealth) {
+        let status = &mut self.statuses[index];
+        if status.health == health {
+            return;
+        }
+        match &health {
+            WatchHealth::Active => info!("Watch root {} is back", status.path.display()),
+            WatchHealth::Missing => warn!(
+                "Watch root {} is missing, retrying every {}s",
+                status.path.display(),
+                HEALTH_CHECK_INTERVAL.as_secs()
+            ),
+            WatchHealth::Error(e) => warn!(
+                "Failed to watch {}: {} (retrying every {}s)",
+                status.path.display(),
+                e,
+                HEALTH_CHECK_INTERVAL.as_secs()
+            ),
+        }
+        status.health = health;
+        status.since = Local::now();
+        self.status_dirty = true;
+    }
+
+    fn save_statuses(&mut self) {
+        let Some(ref file) = self.status_file else {
+            return;
+        };
+        match WatchStatus::save(file, &self.statuses) {
+            Ok(()) => self.status_dirty = false,
+            Err(e) => warn!("Failed to save watch status: {:#}", e),
+        }
+    }
+
+    /// Set up the re-scans a watch and the rules it allows ask for
+    fn schedule_scans(&mut self, root: &Path, watch: &WatchConfig) {
+        let now = Local::now();
+        let rule_schedules = self
+            .engine
+            .enabled_rules()
+            .filter(|rule| watch.allows_rule(&rule.name))
+            .filter_map(|rule| Some((Some(rule.name.clone()), rule.rescan.clone()?)));
+
+        for (rule, schedule) in watch
+            .rescan
+            .clone()
+            .map(|schedule| (None, schedule))
+            .into_iter()
+            .chain(rule_schedules)
+        {
+            info!(
+                "Rescan schedule for {}: {}{}",
+                root.display(),
+                schedule,
+                rule.as_ref()
+                    .map(|name| format!(" for rule '{}'", name))
+                    .unwrap_or_default()
+            );
+            self.scans.push(ScheduledScan {
+                root: root.to_path_buf(),
+                rule,
+                next: schedule.next_after(now),
+                schedule,
+            });
+        }
+    }
+
     /// Stop watching a directory
     pub fn unwatch(&mut self, path: &Path) -> Result<()> {
-        self.watcher.unwatch(path)?;
+        let path = crate::expand_path(path);
+        let result = self.deregister(&path);
+        self.watches.retain(|w| w.path != path);
+        self.statuses.retain(|status| status.path != path);
+        self.status_dirty = true;
+        self.scans.retain(|scan| scan.root != path);
         info!("Stopped watching: {}", path.display());
-        Ok(())
+        result
     }
 
     /// Process pending events (non-blocking)
@@ -86,41 +451,30 @@ impl Watcher {
     pub fn process_events(&mut self) -> Result<usize> {
         let mut processed = 0;
 
-        for event in self.poll()? {
+        for mut event in self.poll()? {
+            event.paths.retain(|path| !self.is_excluded(path));
+            if event.paths.is_empty() {
+                continue;
+            }
             debug!("Event: {:?}", event.kind);
 
-            // Only process create and modify events
+            self.notice_root_changes(&event);
+            self.follow_tree_changes(&event);
+            self.record_added(&event);
+
+            // Files the rules just moved, renamed or created aren't processed again
+            event.paths.retain(|path| !self.own.contains(path));
+            if event.paths.is_empty() {
+                debug!("Ignoring event caused by an action: {:?}", event.kind);
+                continue;
+            }
+
+            // Only changes count; reading a file doesn't make it worth re-processing
             match event.kind {
                 notify::EventKind::Create(_)
                 | notify::EventKind::Modify(_)
-                | notify::EventKind::Access(_) => {
-                    // Use event handler to debounce
-                    let paths_to_process = self.event_handler.should_process(&event);
-
-                    for path in paths_to_process {
-                        if path.is_file() && path.exists() {
-                            info!("File event detected: {}", path.display());
-                            match self.engine.process(&path) {
-                                Ok(true) => processed += 1,
-                                Ok(false) => {} // No matching rule
-                                Err(e) => {
-                                    error!("Rule processing failed for {}: {}", path.display(), e);
-                                    // Find which rule matched (if any) for the notification
-                                    let rule_name = self
-                                        .engine
-                                        .evaluate(&path)
-                                        .ok()
-                                        .flatten()
-                                        .map(|_| self.find_matching_rule_name(&path))
-                                        .unwrap_or_else(|| "unknown".to_string());
-                                    crate::notifications::notify_rule_error(
-                                        &rule_name,
-                                        &e.to_string(),
-                                    );
-                                }
-                            }
-                        }
-                    }
+                | notify::EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
+                    self.event_handler.record(&event);
                 }
                 _ => {
                     debug!("Ignoring event kind: {:?}", event.kind);
@@ -128,20 +482,292 @@ impl Watcher {
             }
         }
 
+        // Process files once they have been quiet for the debounce window
+        for path in self.event_handler.ready() {
+            if path.is_file() {
+                info!("File event detected: {}", path.display());
+                if self.apply_rules(&path, None) {
+                    processed += 1;
+                }
+            }
+        }
+
         // Periodically clean up old entries
         self.event_handler.cleanup();
+        self.own.cleanup();
+        self.loops.cleanup();
+        self.added.save_if_due();
 
         Ok(processed)
     }
 
-    /// Find the name of the first matching rule for a path
-    fn find_matching_rule_name(&self, path: &std::path::Path) -> String {
-        for rule in self.engine.rules() {
-            if rule.enabled && rule.condition.matches(path).unwrap_or(false) {
-                return rule.name.clone();
+    /// Start due rescans and check the next queued files
+    ///
+    /// At most the scan rate's worth of files is checked per call, so a large
+    /// folder doesn't saturate the disk. Returns the number of files a rule
+    /// acted on. Files processed recently (by an event or another scan) are
+    /// skipped, like repeated events.
+    pub fn run_scans(&mut self) -> Result<usize> {
+        let now = Local::now();
+
+        // Merge the scans due for the same root so each file is checked once
+        let mut due: BTreeMap<PathBuf, Option<HashSet<String>>> = BTreeMap::new();
+        for scan in &mut self.scans {
+            if scan.next.is_none_or(|next| next > now) {
+                continue;
+            }
+            scan.next = scan.schedule.next_after(now);
+
+            let rules = due
+                .entry(scan.root.clone())
+                .or_insert_with(|| Some(HashSet::new()));
+            match (&scan.rule, rules.as_mut()) {
+                (Some(rule), Some(rules)) => {
+                    rules.insert(rule.clone());
+                }
+                _ => *rules = None,
+            }
+        }
+        for (root, rules) in due {
+            self.queue_scan(root, rules);
+        }
+
+        let mut processed = 0;
+        let mut budget = self.scan_rate;
+        while budget > 0
+            && let Some(mut batch) = self.queue.pop_front()
+        {
+            while budget > 0
+                && let Some(path) = batch.files.pop_front()
+            {
+                // The file may have been moved since the scan found it
+                if !path.is_file() || !self.event_handler.should_process_path(&path) {
+                    continue;
+                }
+                budget -= 1;
+                self.added.record_existing(&path);
+                if self.apply_rules(&path, batch.rules.as_ref()) {
+                    processed += 1;
+                }
+            }
+
+            if batch.files.is_empty() {
+                info!("Finished scanning {}", batch.root.display());
+            } else {
+                self.queue.push_front(batch);
+            }
+        }
+
+        Ok(processed)
+    }
+
+    /// Queue the files under a root for checking
+    fn queue_scan(&mut self, root: PathBuf, rules: Option<HashSet<String>>) {
+        if self.queue.iter().any(|batch| batch.root == root) {
+            debug!(
+                "Previous scan of {} still running, skipping",
+                root.display()
+            );
+            return;
+        }
+
+        let Some(active) = self.active.iter().find(|a| a.filter.root() == root) else {
+            return;
+        };
+        let files: VecDeque<_> = walk(&active.filter).into();
+        info!("Scanning {} ({} files)", root.display(), files.len());
+        self.queue.push_back(ScanBatch { root, files, rules });
+    }
+
+    /// Run the applicable rules on a file, returning whether any rule acted
+    ///
+    /// `only` restricts processing to the named rules. Paths the actions
+    /// produce are remembered so their events are ignored, and a file that
+    /// keeps being acted on is stopped by the loop detector.
+    fn apply_rules(&mut self, path: &Path, only: Option<&HashSet<String>>) -> bool {
+        if self.loops.is_stopped(path) {
+            debug!("Skipping {}: stopped for looping", path.display());
+            return false;
+        }
+        if self.own.is_restored(path) {
+            debug!("Skipping {}: put back by undo", path.display());
+            return false;
+        }
+
+        let watches = &self.watches;
+        let base = MatchContext::with_root(watch_root(watches, path).as_deref())
+            .with_added(self.added.get(path));
+        let allow = |rule: &crate::rules::Rule| {
+            only.is_none_or(|only| only.contains(&rule.name))
+                && rule_applies(watches, path, &rule.name)
+        };
+
+        match self.engine.process_with(path, &base, allow) {
+            Ok(report) => {
+                crate::journal::record_report(&report);
+                self.own.remember(&report);
+                if let Some(rules) = self.loops.record(path, &report) {
+                    let rules = rules.join(", ");
+                    error!(
+                        "Rules keep acting on {} (rules: {}); not processing it again until reload",
+                        path.display(),
+                        rules
+                    );
+                    crate::notifications::notify_rule_error(
+                        &rules,
+                        &format!(
+                            "Loop detected on {}; file skipped until reload",
+                            path.display()
+                        ),
+                    );
+                }
+                if let Some(failure) = &report.failure {
+                    error!("{}", failure);
+                    let message = match failure.action {
+                        Some(ref action) => format!(
+                            "Step {} ({}): {}",
+                            failure.step + 1,
+                            action.name(),
+                            failure.error
+                        ),
+                        None => format!("Conditions: {}", failure.error),
+                    };
+                    crate::notifications::notify_rule_error(&failure.rule, &message);
+                }
+                !report.applied.is_empty()
+            }
+            Err(e) => {
+                error!("Rule processing failed for {}: {}", path.display(), e);
+                crate::notifications::notify_rule_error("unknown", &e.to_string());
+                false
+            }
+        }
+    }
+
+    /// Write state that is normally saved periodically (added dates, watch
+    /// statuses)
+    pub fn flush(&mut self) {
+        if let Err(e) = self.added.save() {
+            error!("Failed to save added-date index: {:#}", e);
+        }
+        self.save_statuses();
+    }
+
+    /// Whether every watch containing a path excludes it
+    ///
+    /// Paths outside all watch roots aren't excluded.
+    fn is_excluded(&self, path: &Path) -> bool {
+        let is_dir = path.is_dir();
+        let mut containing = self
+            .active
+            .iter()
+            .filter(|a| path.starts_with(a.filter.root()))
+            .peekable();
+        containing.peek().is_some() && !containing.any(|a| a.filter.allows(path, is_dir))
+    }
+
+    /// Have the roots checked right away when one of them is deleted or
+    /// moved, registering it again even if a new folder took its place
+    fn notice_root_changes(&mut self, event: &notify::Event) {
+        if !matches!(
+            event.kind,
+            notify::EventKind::Remove(_) | notify::EventKind::Modify(ModifyKind::Name(_))
+        ) {
+            return;
+        }
+        for active in &mut self.active {
+            if event.paths.iter().any(|path| path == active.filter.root()) {
+                // A recreated folder can get the old inode number back
+                active.identity = None;
+                self.next_check = Instant::now();
+            }
+        }
+    }
+
+    /// Keep folder-by-folder registrations in step with folders being created,
+    /// moved and deleted, and reload changed .gitignore files
+    fn follow_tree_changes(&mut self, event: &notify::Event) {
+        // Listing folders causes access events, which must not trigger this again
+        if !matches!(
+            event.kind,
+            notify::EventKind::Create(_)
+                | notify::EventKind::Remove(_)
+                | notify::EventKind::Modify(ModifyKind::Name(_) | ModifyKind::Data(_))
+                | notify::EventKind::Access(AccessKind::Close(AccessMode::Write))
+        ) {
+            return;
+        }
+
+        for path in &event.paths {
+            let is_gitignore = path.file_name().is_some_and(|name| name == ".gitignore");
+            let is_dir = path.is_dir();
+
+            for active in &mut self.active {
+                let root = active.filter.root().to_path_buf();
+                if !path.starts_with(&root) {
+                    continue;
+                }
+                if is_gitignore && let Some(dir) = path.parent() {
+                    active.filter.load_gitignore(dir);
+                }
+                if active.folders.is_empty() {
+                    continue;
+                }
+
+                // Deleted or renamed folders lose their watch
+                if !is_dir {
+                    active
+                        .folders
+                        .retain(|f| *f == root || !f.starts_with(path) || f.is_dir());
+                    continue;
+                }
+                if active.filter.allows(path, true) {
+                    for folder in active.filter.folders(path) {
+                        if active.folders.contains(&folder) {
+                            continue;
+                        }
+                        let backend = backend(&mut active.poller, &mut self.watcher);
+                        match backend.watch(&folder, RecursiveMode::NonRecursive) {
+                            Ok(()) => active.folders.push(folder),
+                            Err(e) => warn!("Failed to watch {}: {}", folder.display(), e),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Remember when paths first appeared under a watch root
+    ///
+    /// Paths that are removed or renamed away are forgotten, so a file created
+    /// again under the same name counts as new.
+    fn record_added(&mut self, event: &notify::Event) {
+        let gone = match event.kind {
+            notify::EventKind::Remove(_)
+            | notify::EventKind::Modify(ModifyKind::Name(RenameMode::From)) => &event.paths[..],
+            notify::EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                &event.paths[..event.paths.len().min(1)]
+            }
+            _ => &[],
+        };
+        for path in gone {
+            self.added.forget(path);
+        }
+
+        let created = matches!(event.kind, notify::EventKind::Create(_));
+        for path in &event.paths {
+            if gone.contains(path)
+                || watch_root(&self.watches, path).is_none()
+                || path.symlink_metadata().is_err()
+            {
+                continue;
+            }
+            if created {
+                self.added.record_created(path);
+            } else {
+                self.added.record_existing(path);
             }
         }
-        "unknown".to_string()
     }
 
     /// Get the rule engine
@@ -149,3 +775,45 @@ impl Watcher {
         &self.engine
     }
 }
+
+/// The notify backend a watch's folders are registered with
+fn backend<'a>(
+    poller: &'a mut Option<PollWatcher>,
+    native: &'a mut RecommendedWatcher,
+) -> &'a mut dyn NotifyWatcher {
+    match poller {
+        Some(poller) => poller,
+        None => native,
+    }
+}
+
+/// Health of a watch whose registration failed
+fn health_after_error(root: &Path, error: &anyhow::Error) -> WatchHealth {
+    if root.exists() {
+        WatchHealth::Error(format!("{:#}", error))
+    } else {
+        WatchHealth::Missing
+    }
+}
+
+#[cfg(unix)]
+fn root_identity(root: &Path) -> Option<RootIdentity> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(root)
+        .ok()
+        .map(|meta| (meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn root_identity(root: &Path) -> Option<RootIdentity> {
+    root.exists().then_some((0, 0))
+}
+
+/// Event handler that forwards notify results into the watcher's channel
+fn forward_to(tx: EventSender) -> impl Fn(notify::Result<notify::Event>) + Send + 'static {
+    move |res| {
+        if let Err(e) = tx.send(res) {
+            error!("Failed to send watch event: {}", e);
+        }
+    }
+}
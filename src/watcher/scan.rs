@@ -0,0 +1,83 @@
+//! Walking a watch root to find the files already in it
+
+use std::fs;
+use std::path::PathBuf;
+use tracing::warn;
+
+use super::WatchFilter;
+
+/// Files under a watch root that its filter allows
+///
+/// Symlinked folders are not followed, so a link back up the tree can't loop.
+/// Unreadable folders are logged and skipped.
+pub fn walk(filter: &WatchFilter) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![filter.root().to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Failed to scan {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if file_type.is_dir() {
+                if filter.allows(&path, true) {
+                    pending.push(path);
+                }
+            } else if path.is_file() && filter.allows(&path, false) {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WatchConfig;
+
+    #[test]
+    fn test_walk() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("sub/deeper")).unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("sub/b.txt"), "b").unwrap();
+        fs::write(root.join("sub/deeper/c.txt"), "c").unwrap();
+
+        let filter = |recursive: bool, exclude: &[&str]| {
+            WatchFilter::new(&WatchConfig {
+                path: root.to_path_buf(),
+                recursive,
+                exclude: exclude.iter().map(|e| e.to_string()).collect(),
+                ..Default::default()
+            })
+            .unwrap()
+        };
+
+        assert_eq!(walk(&filter(false, &[])), vec![root.join("a.txt")]);
+        assert_eq!(
+            walk(&filter(true, &[])),
+            vec![
+                root.join("a.txt"),
+                root.join("sub/b.txt"),
+                root.join("sub/deeper/c.txt"),
+            ]
+        );
+        assert_eq!(
+            walk(&filter(true, &["deeper"])),
+            vec![root.join("a.txt"), root.join("sub/b.txt")]
+        );
+    }
+}